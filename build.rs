@@ -6,8 +6,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Tell the build script to only run again if we change our source shaders
     println!("cargo:rerun-if-changed=src/render/shader");
 
-    // Create destination path if necessary
-    std::fs::create_dir_all("src/render/shader")?;
+    // Compiled shaders are build output, so they can never go stale in the source tree
+    let out_dir = std::env::var("OUT_DIR")?;
 
     for entry in std::fs::read_dir("src/render/shader")? {
         let entry = entry?;
@@ -35,7 +35,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 compiled_file.read_to_end(&mut compiled_bytes)?;
 
                 let out_path = format!(
-                    "src/render/shader/{}.spv",
+                    "{}/{}.spv",
+                    out_dir,
                     in_path.file_name().unwrap().to_string_lossy()
                 );
 
@@ -14,8 +14,8 @@ impl runtime::State<Event> for State {
     fn update(&mut self, event: Event) {
         match event {
             Event::DeleteGraphic(canvas) => {
-                // for mesh in &mut self.main_canvas.meshes {
-                //     self.main_canvas.delete(&mesh);
+                // for mesh in self.main_canvas.meshes().collect::<Vec<_>>() {
+                //     self.main_canvas.delete(&mesh).unwrap();
                 // }
             }
             Event::CreateGraphic(mut canvas) => {
@@ -38,7 +38,7 @@ impl runtime::State<Event> for State {
                         let r = rng.gen::<f32>(); // * x.abs() / x_size;
                         let g = rng.gen::<f32>(); // * y.abs() / y_size;
                         let b = rng.gen::<f32>(); // * (x.abs() + y.abs()) / (x_size + y_size);
-                        canvas.color(&m, [r, g, b, 1.0]);
+                        canvas.color(&m, [r, g, b, 1.0]).unwrap();
                     }
                 }
             }
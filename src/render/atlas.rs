@@ -0,0 +1,172 @@
+use super::math::*;
+use super::texture::TexelRect;
+
+/// Size of the atlas pages that small textures are packed into.
+pub(crate) const PAGE_SIZE: u32 = 1024;
+/// Textures larger than this in either dimension get a texture of their own.
+pub(crate) const MAX_PACKED_SIZE: u32 = 256;
+/// Gap around images packed into a page, filled with their edge pixels.
+pub(crate) const PAGE_PADDING: u32 = 2;
+
+/// Packs rectangles into a fixed size area, row by row.
+///
+/// Each row ("shelf") is as tall as the first rectangle placed in it, rectangles are placed
+/// left to right until the row is full and a new row is started below it.
+/// This wastes some space when heights vary a lot but is fast and works well for glyphs
+/// and sprites which tend to have similar heights.
+#[derive(Clone, Debug)]
+pub(crate) struct ShelfPacker {
+    width: u32,
+    height: u32,
+    /// Gap left around every rectangle so filtering doesn't bleed between neighbours.
+    padding: u32,
+    shelves: Vec<Shelf>,
+}
+
+#[derive(Clone, Debug)]
+struct Shelf {
+    y: u32,
+    height: u32,
+    x: u32,
+}
+
+impl ShelfPacker {
+    pub fn new(width: u32, height: u32, padding: u32) -> Self {
+        Self {
+            width,
+            height,
+            padding,
+            shelves: Vec::new(),
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Reserves space for a rectangle and returns the position of its top left corner,
+    /// or `None` if there is no space left.
+    pub fn pack(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let padded_width = width + self.padding * 2;
+        let padded_height = height + self.padding * 2;
+        if padded_width > self.width {
+            return None;
+        }
+        // Use the shortest shelf the rectangle fits in to waste as little height as possible
+        let width = self.width;
+        let shelf = self
+            .shelves
+            .iter_mut()
+            .filter(|shelf| shelf.height >= padded_height && shelf.x + padded_width <= width)
+            .min_by_key(|shelf| shelf.height);
+        let shelf = match shelf {
+            Some(shelf) => shelf,
+            None => {
+                let y = self
+                    .shelves
+                    .last()
+                    .map_or(0, |shelf| shelf.y + shelf.height);
+                if y + padded_height > self.height {
+                    return None;
+                }
+                self.shelves.push(Shelf {
+                    y,
+                    height: padded_height,
+                    x: 0,
+                });
+                self.shelves.last_mut().unwrap()
+            }
+        };
+        let position = (shelf.x + self.padding, shelf.y + self.padding);
+        shelf.x += padded_width;
+        Some(position)
+    }
+}
+
+/// Copies the image into the page with its top left corner at (x, y),
+/// returns the rectangle of the page that changed.
+pub(crate) fn blit(
+    page: &mut image::RgbaImage,
+    image: &image::RgbaImage,
+    x: u32,
+    y: u32,
+) -> TexelRect {
+    image::imageops::replace(page, image, x, y);
+    let (width, height) = image.dimensions();
+    extrude(
+        page,
+        TexelRect {
+            x,
+            y,
+            width,
+            height,
+        },
+    )
+}
+
+/// Repeats the edge pixels of the rectangle into the padding around it, so filtering near
+/// the edges samples the image itself rather than its neighbours in the page.
+///
+/// Returns the rectangle including its padding.
+pub(crate) fn extrude(page: &mut image::RgbaImage, rect: TexelRect) -> TexelRect {
+    let TexelRect {
+        x,
+        y,
+        width,
+        height,
+    } = rect;
+    let padded = TexelRect {
+        x: x - PAGE_PADDING,
+        y: y - PAGE_PADDING,
+        width: width + PAGE_PADDING * 2,
+        height: height + PAGE_PADDING * 2,
+    };
+    if width == 0 || height == 0 {
+        return padded;
+    }
+    for py in padded.y..padded.y + padded.height {
+        for px in padded.x..padded.x + padded.width {
+            let inside = (x..x + width).contains(&px) && (y..y + height).contains(&py);
+            if !inside {
+                let edge =
+                    *page.get_pixel(px.max(x).min(x + width - 1), py.max(y).min(y + height - 1));
+                page.put_pixel(px, py, edge);
+            }
+        }
+    }
+    padded
+}
+
+/// Returns the region of the page covered by the image at (x, y) in texture coordinates.
+pub(crate) fn region(page: &image::RgbaImage, x: u32, y: u32, width: u32, height: u32) -> Rect {
+    let (page_width, page_height) = page.dimensions();
+    Rect {
+        min: [x as f32 / page_width as f32, y as f32 / page_height as f32],
+        max: [
+            (x + width) as f32 / page_width as f32,
+            (y + height) as f32 / page_height as f32,
+        ],
+    }
+}
+
+/// Region covering a whole texture.
+pub(crate) const WHOLE: Rect = Rect {
+    min: [0.0, 0.0],
+    max: [1.0, 1.0],
+};
+
+/// Returns the part of the region covered by `sub`, which is relative to the region.
+pub(crate) fn subregion(region: &Rect, sub: &Rect) -> Rect {
+    Rect {
+        min: to_region(region, sub.min),
+        max: to_region(region, sub.max),
+    }
+}
+
+/// Maps texture coordinates of a whole image to the region it is packed into.
+pub(crate) fn to_region(region: &Rect, [u, v]: Vec2) -> Vec2 {
+    [
+        region.min[0] + u * region.width(),
+        region.min[1] + v * region.height(),
+    ]
+}
@@ -1,12 +1,16 @@
 use super::math::*;
 
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Camera {
     pub eye: Vec3,
     pub target: Vec3,
     pub up: Vec3,
     pub projection: Projection,
+    /// How the projection is adjusted to the size of the render target.
+    pub mode: ProjectionMode,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Projection {
     Perspective {
         aspect: f32,
@@ -14,6 +18,7 @@ pub enum Projection {
         near: f32,
         far: f32,
     },
+    /// The bounds are relative to the eye.
     Orthographic {
         left: f32,
         right: f32,
@@ -24,6 +29,46 @@ pub enum Projection {
     },
 }
 
+/// How a camera's projection is adjusted to the size of the render target,
+/// recomputed whenever the target is resized.
+///
+/// Perspective projections take the aspect ratio of the target in every mode but `Stretch`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ProjectionMode {
+    /// Maps the orthographic bounds onto the whole target, stretching content when
+    /// the aspect ratios differ.
+    Stretch,
+    /// Shows exactly the orthographic bounds with their aspect ratio, leaving black bars
+    /// above and below or to the sides of them.
+    Fit,
+    /// Fills the whole target without stretching, cutting off the parts of the orthographic
+    /// bounds that don't fit.
+    Fill,
+    /// One unit is one physical pixel while the orthographic bounds are 2 units wide,
+    /// zooming scales from there. The view is centered on the center of the bounds.
+    Pixels,
+}
+
+/// Looks down the z axis at the origin and shows -1 to 1 on both axes.
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            eye: [0.0, 0.0, 1.0],
+            target: [0.0, 0.0, 0.0],
+            up: [0.0, 1.0, 0.0],
+            projection: Projection::Orthographic {
+                left: -1.0,
+                right: 1.0,
+                bottom: -1.0,
+                top: 1.0,
+                near: -1.0,
+                far: 2.0,
+            },
+            mode: ProjectionMode::Fit,
+        }
+    }
+}
+
 //TODO: remove cgmath from camera and abstract to math module
 impl Camera {
     pub fn build_view_projection_matrix(&self) -> Mat4 {
@@ -46,4 +91,401 @@ impl Camera {
         };
         return (proj * view).into();
     }
+
+    /// Builds the view projection for a target with the size, adjusted by the mode.
+    pub fn view_projection(&self, width: u32, height: u32) -> Mat4 {
+        Camera {
+            projection: self.projection_for(width, height),
+            ..*self
+        }
+        .build_view_projection_matrix()
+    }
+
+    /// Returns the projection adjusted by the mode for a target with the size.
+    pub fn projection_for(&self, width: u32, height: u32) -> Projection {
+        if width == 0 || height == 0 {
+            return self.projection;
+        }
+        let target_aspect = width as f32 / height as f32;
+        match (self.mode, self.projection) {
+            // Fit letterboxes orthographic bounds with the viewport instead
+            (ProjectionMode::Stretch, projection)
+            | (ProjectionMode::Fit, projection @ Projection::Orthographic { .. }) => projection,
+            (
+                _,
+                Projection::Perspective {
+                    fovy, near, far, ..
+                },
+            ) => Projection::Perspective {
+                aspect: target_aspect,
+                fovy,
+                near,
+                far,
+            },
+            (
+                mode,
+                Projection::Orthographic {
+                    left,
+                    right,
+                    bottom,
+                    top,
+                    near,
+                    far,
+                },
+            ) => {
+                let center = [(left + right) * 0.5, (bottom + top) * 0.5];
+                let (half_width, half_height) = if mode == ProjectionMode::Pixels {
+                    let scale = (right - left) * 0.5;
+                    (width as f32 * 0.5 * scale, height as f32 * 0.5 * scale)
+                } else if target_aspect > (right - left) / (top - bottom) {
+                    // The target is wider than the bounds, the top and bottom are cut off
+                    let half_width = (right - left) * 0.5;
+                    (half_width, half_width / target_aspect)
+                } else {
+                    let half_height = (top - bottom) * 0.5;
+                    (half_height * target_aspect, half_height)
+                };
+                Projection::Orthographic {
+                    left: center[0] - half_width,
+                    right: center[0] + half_width,
+                    bottom: center[1] - half_height,
+                    top: center[1] + half_height,
+                    near,
+                    far,
+                }
+            }
+        }
+    }
+
+    /// Returns the area of a target with the size that the camera draws to, in pixels.
+    ///
+    /// This is the whole target unless the mode is `Fit`, which letterboxes orthographic bounds
+    /// with a different aspect ratio than the target.
+    pub fn viewport(&self, width: u32, height: u32) -> Rect {
+        let (target_width, target_height) = (width as f32, height as f32);
+        let whole = Rect {
+            min: [0.0, 0.0],
+            max: [target_width, target_height],
+        };
+        let aspect = match (self.mode, self.projection) {
+            (
+                ProjectionMode::Fit,
+                Projection::Orthographic {
+                    left,
+                    right,
+                    bottom,
+                    top,
+                    ..
+                },
+            ) => (right - left) / (top - bottom),
+            _ => return whole,
+        };
+        let (view_width, view_height) = if target_width > target_height * aspect {
+            (target_height * aspect, target_height)
+        } else {
+            (target_width, target_width / aspect)
+        };
+        let min = [
+            ((target_width - view_width) * 0.5).floor(),
+            ((target_height - view_height) * 0.5).floor(),
+        ];
+        Rect {
+            min,
+            max: [min[0] + view_width, min[1] + view_height],
+        }
+    }
+
+    /// Moves the eye and target along the x and y axes, the view slides by the same amount.
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        self.eye[0] += dx;
+        self.eye[1] += dy;
+        self.target[0] += dx;
+        self.target[1] += dy;
+    }
+
+    /// Zooms in by the factor, or out if it is less than 1, keeping the point in the target
+    /// plane at the same place on screen.
+    ///
+    /// Factors that aren't positive and finite are ignored.
+    pub fn zoom_at(&mut self, factor: f32, [x, y]: Vec2) {
+        if !(factor > 0.0 && factor.is_finite()) {
+            return;
+        }
+        let eye = self.eye;
+        // The eye moves towards the point by the same factor the view shrinks
+        self.eye[0] = x - (x - eye[0]) / factor;
+        self.eye[1] = y - (y - eye[1]) / factor;
+        self.target[0] += self.eye[0] - eye[0];
+        self.target[1] += self.eye[1] - eye[1];
+        match &mut self.projection {
+            Projection::Perspective { .. } => {
+                self.eye[2] = self.target[2] + (eye[2] - self.target[2]) / factor;
+            }
+            Projection::Orthographic {
+                left,
+                right,
+                bottom,
+                top,
+                ..
+            } => {
+                *left /= factor;
+                *right /= factor;
+                *bottom /= factor;
+                *top /= factor;
+            }
+        }
+    }
+
+    /// Centers the view on the bounds and zooms so they are just visible,
+    /// keeping the aspect ratio of the projection.
+    ///
+    /// Bounds that are a single point are only centered, there is nothing to zoom to.
+    pub fn fit(&mut self, bounds: &Rect) {
+        let center = [
+            (bounds.min[0] + bounds.max[0]) * 0.5,
+            (bounds.min[1] + bounds.max[1]) * 0.5,
+        ];
+        self.pan(center[0] - self.target[0], center[1] - self.target[1]);
+        if !(bounds.width() > 0.0 || bounds.height() > 0.0) {
+            return;
+        }
+        match &mut self.projection {
+            Projection::Perspective { aspect, fovy, .. } => {
+                let half_height = (bounds.height() * 0.5).max(bounds.width() * 0.5 / *aspect);
+                let distance = half_height / (fovy.to_radians() * 0.5).tan();
+                self.eye[2] = self.target[2] + distance;
+            }
+            Projection::Orthographic {
+                left,
+                right,
+                bottom,
+                top,
+                ..
+            } => {
+                let aspect = (*right - *left) / (*top - *bottom);
+                let half_height = (bounds.height() * 0.5).max(bounds.width() * 0.5 / aspect);
+                let half_width = half_height * aspect;
+                *left = -half_width;
+                *right = half_width;
+                *bottom = -half_height;
+                *top = half_height;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-5,
+            "{} is not close to {}",
+            actual,
+            expected
+        );
+    }
+
+    fn perspective() -> Camera {
+        Camera {
+            eye: [0.0, 0.0, 2.0],
+            projection: Projection::Perspective {
+                aspect: 1.0,
+                fovy: 90.0,
+                near: 0.1,
+                far: 10.0,
+            },
+            ..Camera::default()
+        }
+    }
+
+    fn orthographic(mode: ProjectionMode, left: f32, right: f32, bottom: f32, top: f32) -> Camera {
+        Camera {
+            projection: Projection::Orthographic {
+                left,
+                right,
+                bottom,
+                top,
+                near: -1.0,
+                far: 2.0,
+            },
+            mode,
+            ..Camera::default()
+        }
+    }
+
+    fn bounds(projection: Projection) -> [f32; 4] {
+        match projection {
+            Projection::Orthographic {
+                left,
+                right,
+                bottom,
+                top,
+                ..
+            } => [left, right, bottom, top],
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn adjusts_orthographic_projections() {
+        let stretch = orthographic(ProjectionMode::Stretch, -1.0, 1.0, -1.0, 1.0);
+        assert_eq!(stretch.projection_for(200, 100), stretch.projection);
+        let fit = orthographic(ProjectionMode::Fit, -1.0, 1.0, -1.0, 1.0);
+        assert_eq!(fit.projection_for(200, 100), fit.projection);
+
+        let fill = orthographic(ProjectionMode::Fill, -1.0, 1.0, -1.0, 1.0);
+        assert_eq!(
+            bounds(fill.projection_for(200, 100)),
+            [-1.0, 1.0, -0.5, 0.5]
+        );
+        assert_eq!(
+            bounds(fill.projection_for(100, 200)),
+            [-0.5, 0.5, -1.0, 1.0]
+        );
+
+        let pixels = orthographic(ProjectionMode::Pixels, 1.0, 3.0, 0.0, 2.0);
+        assert_eq!(
+            bounds(pixels.projection_for(200, 100)),
+            [-98.0, 102.0, -49.0, 51.0]
+        );
+
+        // Empty targets, like minimized windows, keep the projection
+        assert_eq!(fill.projection_for(0, 100), fill.projection);
+    }
+
+    #[test]
+    fn perspective_takes_the_target_aspect() {
+        for &mode in &[
+            ProjectionMode::Fit,
+            ProjectionMode::Fill,
+            ProjectionMode::Pixels,
+        ] {
+            let camera = Camera {
+                mode,
+                ..perspective()
+            };
+            match camera.projection_for(200, 100) {
+                Projection::Perspective { aspect, .. } => assert_eq!(aspect, 2.0),
+                _ => unreachable!(),
+            }
+            assert_eq!(
+                camera.viewport(200, 100),
+                Rect {
+                    min: [0.0, 0.0],
+                    max: [200.0, 100.0],
+                }
+            );
+        }
+        let stretch = Camera {
+            mode: ProjectionMode::Stretch,
+            ..perspective()
+        };
+        assert_eq!(stretch.projection_for(200, 100), stretch.projection);
+    }
+
+    #[test]
+    fn fit_letterboxes_the_viewport() {
+        let camera = orthographic(ProjectionMode::Fit, -1.0, 1.0, -1.0, 1.0);
+        assert_eq!(
+            camera.viewport(200, 100),
+            Rect {
+                min: [50.0, 0.0],
+                max: [150.0, 100.0],
+            }
+        );
+        assert_eq!(
+            camera.viewport(100, 201),
+            Rect {
+                min: [0.0, 50.0],
+                max: [100.0, 150.0],
+            }
+        );
+
+        let fill = orthographic(ProjectionMode::Fill, -1.0, 1.0, -1.0, 1.0);
+        assert_eq!(
+            fill.viewport(200, 100),
+            Rect {
+                min: [0.0, 0.0],
+                max: [200.0, 100.0],
+            }
+        );
+    }
+
+    #[test]
+    fn zooms_at_a_point() {
+        let mut camera = Camera::default();
+        camera.zoom_at(2.0, [1.0, 1.0]);
+        assert_eq!(camera.eye[..2], [0.5, 0.5]);
+        assert_eq!(camera.target[..2], [0.5, 0.5]);
+        match camera.projection {
+            Projection::Orthographic { left, right, .. } => assert_eq!((left, right), (-0.5, 0.5)),
+            _ => unreachable!(),
+        }
+
+        let mut camera = perspective();
+        camera.zoom_at(4.0, [0.0, 0.0]);
+        assert_close(camera.eye[2], 0.5);
+    }
+
+    #[test]
+    fn zoom_ignores_invalid_factors() {
+        for &factor in &[0.0, -2.0, f32::INFINITY, f32::NAN] {
+            let mut camera = Camera::default();
+            camera.zoom_at(factor, [1.0, 1.0]);
+            assert_eq!(camera, Camera::default());
+        }
+    }
+
+    #[test]
+    fn fits_bounds() {
+        let bounds = Rect {
+            min: [1.0, 2.0],
+            max: [5.0, 4.0],
+        };
+        let mut camera = Camera::default();
+        camera.fit(&bounds);
+        assert_eq!(camera.eye[..2], [3.0, 3.0]);
+        match camera.projection {
+            Projection::Orthographic {
+                left,
+                right,
+                bottom,
+                top,
+                ..
+            } => assert_eq!((left, right, bottom, top), (-2.0, 2.0, -2.0, 2.0)),
+            _ => unreachable!(),
+        }
+
+        let mut camera = perspective();
+        camera.fit(&bounds);
+        assert_eq!(camera.target[..2], [3.0, 3.0]);
+        assert_close(camera.eye[2], 2.0);
+    }
+
+    #[test]
+    fn fit_only_centers_points() {
+        let point = Rect {
+            min: [1.0, 1.0],
+            max: [1.0, 1.0],
+        };
+        for &camera in &[Camera::default(), perspective()] {
+            let mut fitted = camera;
+            fitted.fit(&point);
+            assert_eq!(fitted.eye, [1.0, 1.0, camera.eye[2]]);
+            assert_eq!(fitted.projection, camera.projection);
+        }
+
+        // A line still has a length to fit
+        let mut camera = Camera::default();
+        camera.fit(&Rect {
+            min: [0.0, 0.0],
+            max: [4.0, 0.0],
+        });
+        match camera.projection {
+            Projection::Orthographic { left, right, .. } => assert_eq!((left, right), (-2.0, 2.0)),
+            _ => unreachable!(),
+        }
+    }
 }
@@ -1,3 +1,4 @@
+use super::camera::Camera;
 use super::mesh::Canvas;
 use crate::render::renderer::Renderer;
 use winit::dpi::PhysicalSize;
@@ -11,9 +12,10 @@ use winit::{
 /// [`Display`]: struct.Display.html
 pub struct Display {
     window: winit::window::Window,
-    surface: wgpu::Surface,
     renderer: Renderer,
     pub canvas: Canvas,
+    /// The view of the canvas, changes are uploaded with the next frame.
+    pub camera: Camera,
 }
 
 /// [`Display`]: struct.Display.html
@@ -30,38 +32,25 @@ impl Display {
         let size = window.inner_size();
 
         let surface = unsafe { instance.create_surface(&window) };
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::Default,
-                compatible_surface: Some(&surface),
-            })
+        let adapter = Renderer::request_adapter(instance, Some(&surface))
             .await
             .unwrap();
+        let (device, queue) = Renderer::request_device(&adapter).await.unwrap();
 
-        let trace_dir = std::env::var("WGPU_TRACE");
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    shader_validation: true,
-                    ..Default::default()
-                },
-                trace_dir.ok().as_ref().map(std::path::Path::new),
-            )
-            .await
-            .unwrap();
-
-        let renderer = Renderer::new(&surface, device, queue, size.width, size.height);
+        let renderer = Renderer::new(surface, device, queue, size.width, size.height);
 
         let canvas = Canvas::new();
+        let camera = Camera::default();
 
         Self {
             window,
-            surface,
             renderer,
             canvas,
+            camera,
         }
     }
 
+    /// Resizes the swap chain, the camera projection is adjusted to the new size with the next frame.
     pub fn resize(&mut self, size: &PhysicalSize<u32>) {
         log::info!("Resizing to {:?}", size);
         self.renderer.resize(size.width, size.height);
@@ -69,12 +58,10 @@ impl Display {
 
     pub fn draw(&mut self, window_id: winit::window::WindowId) {
         if window_id == self.window.id() {
-            self.renderer.draw_frame(
-                &self.surface,
-                self.canvas.vertices.as_slice(),
-                self.canvas.indices.as_slice(),
-                self.canvas.transforms.as_slice(),
-            );
+            // A failed frame is skipped, the next one tries again
+            if let Err(err) = self.renderer.draw_frame(&mut self.canvas, &self.camera) {
+                log::error!("Failed to draw a frame: {}", err);
+            }
         }
     }
 
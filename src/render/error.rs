@@ -1,5 +1,87 @@
+use super::mesh::Mesh;
+use super::texture::TextureHandle;
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error(transparent)]
     ImageError(#[from] image::ImageError),
+    #[error(transparent)]
+    RequestDeviceError(#[from] wgpu::RequestDeviceError),
+    #[error(transparent)]
+    BufferAsyncError(#[from] wgpu::BufferAsyncError),
+    #[error(transparent)]
+    SvgError(#[from] roxmltree::Error),
+    #[error(transparent)]
+    FontError(#[from] ab_glyph::InvalidFont),
+    #[error("invalid SVG {0}")]
+    InvalidSvg(String),
+    #[error("no graphics adapter is available")]
+    AdapterNotFound,
+    #[error("renderer does not have an offscreen target")]
+    NotHeadless,
+    #[error("mesh {0:?} does not exist in this canvas, it may have been deleted")]
+    MeshNotFound(Mesh),
+    #[error("texture {0:?} does not exist in this canvas")]
+    TextureNotFound(TextureHandle),
+    #[error("texture is {width}x{height} pixels, both sides must be between 1 and {max}")]
+    TextureSize { width: u32, height: u32, max: u32 },
+    #[error("anisotropy must be 1, 2, 4, 8 or 16, got {0}")]
+    Anisotropy(u8),
+    #[error(
+        "{width}x{height} pixel update at ({x}, {y}) does not fit in a {size:?} pixel texture"
+    )]
+    UpdateOutOfBounds {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        size: (u32, u32),
+    },
+    #[error("frame {frame} is out of bounds for a sprite sheet with {len} frames")]
+    FrameOutOfBounds { frame: usize, len: usize },
+    #[error(
+        "frame {frame} is {width}x{height} pixels at ({x}, {y}), which does not fit in a {size:?} pixel image"
+    )]
+    FrameOutsideImage {
+        frame: usize,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        size: (u32, u32),
+    },
+    #[error("animation has no frames")]
+    EmptyAnimation,
+    #[error("mesh has {0} indices, which is not a multiple of 3")]
+    IndexCount(usize),
+    #[error("index {index} is out of bounds for a mesh with {len} vertices")]
+    IndexOutOfBounds { index: u32, len: usize },
+    #[error("polygon needs at least 3 points, got {0}")]
+    PolygonPoints(usize),
+    #[error("polygon is not simple, its edges intersect")]
+    PolygonNotSimple,
+    #[error("mesh has {count} {attribute} but {expected} positions")]
+    AttributeCount {
+        attribute: &'static str,
+        count: usize,
+        expected: usize,
+    },
+    #[error("reference image '{name}' does not exist at {reference_path:?}, set MAPLE_BLESS=1 to create it")]
+    GoldenMissing {
+        name: String,
+        reference_path: std::path::PathBuf,
+    },
+    #[error("image '{name}' is {actual:?} but the reference is {expected:?}")]
+    GoldenSizeMismatch {
+        name: String,
+        expected: (u32, u32),
+        actual: (u32, u32),
+    },
+    #[error("image '{name}' has {mismatched} mismatched pixels (max difference {max_difference}), see {diff_path:?}")]
+    GoldenMismatch {
+        name: String,
+        mismatched: usize,
+        max_difference: u8,
+        diff_path: std::path::PathBuf,
+    },
 }
@@ -0,0 +1,219 @@
+//! A CPU side pixel buffer for drawing pixel by pixel, like the screen of an emulator.
+use super::error::Error;
+use super::math::*;
+use super::texture::{self, TextureOptions};
+
+/// How a framebuffer is scaled to fill the render target.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Scaling {
+    /// Fills the whole target, pixels are stretched if the aspect ratios differ.
+    Stretch,
+    /// Scales as large as the target allows while keeping the aspect ratio,
+    /// the remaining space is left black above and below or to the sides.
+    Fit,
+    /// Like `Fit` but only scales by whole numbers, so every pixel covers the same number of
+    /// target pixels. Falls back to `Fit` when the target is smaller than the framebuffer.
+    Integer,
+}
+
+/// Pixels with a fixed logical resolution that are drawn behind the meshes of a canvas,
+/// scaled to fill the render target.
+///
+/// The pixels are uploaded to the GPU before each frame in which they were changed.
+pub struct Framebuffer {
+    pixels: image::RgbaImage,
+    scaling: Scaling,
+    version: u64,
+    dirty: bool,
+}
+
+impl Framebuffer {
+    /// Creates a black framebuffer with the resolution, scaled by whole numbers.
+    pub fn new(width: u32, height: u32) -> Result<Self, Error> {
+        let pixels = image::RgbaImage::from_pixel(width, height, image::Rgba([0, 0, 0, 255]));
+        texture::check_size(&pixels)?;
+        Ok(Self {
+            pixels,
+            scaling: Scaling::Integer,
+            version: texture::next_version(),
+            dirty: false,
+        })
+    }
+
+    pub fn with_scaling(mut self, scaling: Scaling) -> Self {
+        self.scaling = scaling;
+        self
+    }
+
+    pub fn width(&self) -> u32 {
+        self.pixels.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.pixels.height()
+    }
+
+    pub fn scaling(&self) -> Scaling {
+        self.scaling
+    }
+
+    pub fn set_scaling(&mut self, scaling: Scaling) {
+        self.scaling = scaling;
+    }
+
+    pub fn pixels(&self) -> &image::RgbaImage {
+        &self.pixels
+    }
+
+    /// Gives access to the pixels, which are uploaded again with the next frame.
+    pub fn pixels_mut(&mut self) -> &mut image::RgbaImage {
+        self.dirty = true;
+        &mut self.pixels
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> image::Rgba<u8> {
+        *self.pixels.get_pixel(x, y)
+    }
+
+    /// Sets the pixel at (x, y), counted from the top left corner.
+    ///
+    /// # Panics
+    ///
+    /// Panics if (x, y) is outside of the framebuffer.
+    pub fn put_pixel(&mut self, x: u32, y: u32, pixel: image::Rgba<u8>) {
+        self.pixels_mut().put_pixel(x, y, pixel);
+    }
+
+    /// Sets every pixel to the color.
+    pub fn clear(&mut self, pixel: image::Rgba<u8>) {
+        for p in self.pixels_mut().pixels_mut() {
+            *p = pixel;
+        }
+    }
+
+    /// Pixels are sampled without filtering so they stay sharp when scaled,
+    /// and double buffered as they usually change every frame.
+    pub(crate) fn options(&self) -> TextureOptions {
+        TextureOptions::nearest().with_double_buffering(true)
+    }
+
+    /// Changes when the framebuffer is replaced, so its texture has to be created again.
+    pub(crate) fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Returns true if the pixels changed since the last upload.
+    pub(crate) fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub(crate) fn clear_dirty(&mut self) {
+        self.dirty = false;
+    }
+
+    /// Returns the area of a target with the size that the framebuffer is drawn to, in pixels.
+    pub(crate) fn viewport(&self, width: u32, height: u32) -> Rect {
+        let (target_width, target_height) = (width as f32, height as f32);
+        let (pixels_width, pixels_height) = (self.width() as f32, self.height() as f32);
+        let fit = (target_width / pixels_width).min(target_height / pixels_height);
+        let scale = match self.scaling {
+            Scaling::Stretch => {
+                return Rect {
+                    min: [0.0, 0.0],
+                    max: [target_width, target_height],
+                }
+            }
+            Scaling::Fit => fit,
+            Scaling::Integer if fit >= 1.0 => fit.floor(),
+            Scaling::Integer => fit,
+        };
+        let size = [pixels_width * scale, pixels_height * scale];
+        // Centered on whole pixels so integer scaled pixels line up with the target's
+        let min = [
+            ((target_width - size[0]) * 0.5).floor(),
+            ((target_height - size[1]) * 0.5).floor(),
+        ];
+        Rect {
+            min,
+            max: [min[0] + size[0], min[1] + size[1]],
+        }
+    }
+}
+
+impl std::fmt::Debug for Framebuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // The pixels are too many to be useful in debug output
+        f.debug_struct("Framebuffer")
+            .field("dimensions", &self.pixels.dimensions())
+            .field("scaling", &self.scaling)
+            .field("version", &self.version)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn viewport(size: (u32, u32), scaling: Scaling, target: (u32, u32)) -> Rect {
+        Framebuffer::new(size.0, size.1)
+            .unwrap()
+            .with_scaling(scaling)
+            .viewport(target.0, target.1)
+    }
+
+    #[test]
+    fn integer_scaling_uses_whole_numbers() {
+        assert_eq!(
+            viewport((320, 180), Scaling::Integer, (1000, 600)),
+            Rect {
+                min: [20.0, 30.0],
+                max: [980.0, 570.0],
+            }
+        );
+    }
+
+    #[test]
+    fn integer_scaling_fits_smaller_targets() {
+        assert_eq!(
+            viewport((320, 180), Scaling::Integer, (160, 120)),
+            viewport((320, 180), Scaling::Fit, (160, 120))
+        );
+        assert_eq!(
+            viewport((320, 180), Scaling::Integer, (160, 120)),
+            Rect {
+                min: [0.0, 15.0],
+                max: [160.0, 105.0],
+            }
+        );
+    }
+
+    #[test]
+    fn odd_sizes_are_centered_on_whole_pixels() {
+        assert_eq!(
+            viewport((3, 3), Scaling::Integer, (10, 7)),
+            Rect {
+                min: [2.0, 0.0],
+                max: [8.0, 6.0],
+            }
+        );
+        assert_eq!(
+            viewport((4, 2), Scaling::Fit, (9, 9)),
+            Rect {
+                min: [0.0, 2.0],
+                max: [9.0, 6.5],
+            }
+        );
+    }
+
+    #[test]
+    fn stretch_fills_the_target() {
+        assert_eq!(
+            viewport((320, 180), Scaling::Stretch, (333, 999)),
+            Rect {
+                min: [0.0, 0.0],
+                max: [333.0, 999.0],
+            }
+        );
+    }
+}
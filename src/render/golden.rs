@@ -0,0 +1,179 @@
+use super::error::Error;
+use std::path::{Path, PathBuf};
+
+/// Environment variable that makes missing or different reference images be written
+/// from the rendered image instead of failing the comparison.
+pub const BLESS_VAR: &str = "MAPLE_BLESS";
+
+/// Compares rendered images against stored reference images ("goldens").
+///
+/// References are stored as `<dir>/<name>.png`.
+/// When a comparison fails the rendered image and a diff image are written next to the reference
+/// as `<name>.actual.png` and `<name>.diff.png` so the failure can be inspected.
+///
+/// A missing reference is an error so an uncommitted reference can't make a check pass,
+/// set `MAPLE_BLESS=1` to create references or overwrite them after an intended change to the output.
+pub struct Golden {
+    dir: PathBuf,
+    tolerance: u8,
+}
+
+/// Result of comparing two images pixel by pixel.
+#[derive(Debug)]
+pub struct Comparison {
+    /// Number of pixels that differ by more than the tolerance in any channel.
+    pub mismatched: usize,
+    /// Largest difference found in any channel of any pixel.
+    pub max_difference: u8,
+    /// Mismatched pixels are red, matching pixels are a faded copy of the expected image.
+    pub diff: image::RgbaImage,
+}
+
+impl Golden {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into(),
+            tolerance: 0,
+        }
+    }
+
+    /// Sets the largest difference allowed in any color channel before a pixel counts as mismatched.
+    pub fn with_tolerance(mut self, tolerance: u8) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub fn reference_path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.png", name))
+    }
+
+    /// Checks the image against the reference image with the given name.
+    pub fn check(&self, name: &str, actual: &image::RgbaImage) -> Result<(), Error> {
+        let reference_path = self.reference_path(name);
+        if std::env::var_os(BLESS_VAR).is_some() {
+            log::info!("Writing reference image {:?}", reference_path);
+            std::fs::create_dir_all(&self.dir).map_err(image::ImageError::from)?;
+            actual.save(&reference_path)?;
+            return Ok(());
+        }
+        if !reference_path.exists() {
+            std::fs::create_dir_all(&self.dir).map_err(image::ImageError::from)?;
+            self.save_actual(name, actual)?;
+            return Err(Error::GoldenMissing {
+                name: name.to_string(),
+                reference_path,
+            });
+        }
+
+        let expected = image::open(&reference_path)?.to_rgba();
+        if expected.dimensions() != actual.dimensions() {
+            self.save_actual(name, actual)?;
+            return Err(Error::GoldenSizeMismatch {
+                name: name.to_string(),
+                expected: expected.dimensions(),
+                actual: actual.dimensions(),
+            });
+        }
+
+        let comparison = compare(&expected, actual, self.tolerance);
+        if comparison.mismatched > 0 {
+            self.save_actual(name, actual)?;
+            let diff_path = self.dir.join(format!("{}.diff.png", name));
+            comparison.diff.save(&diff_path)?;
+            return Err(Error::GoldenMismatch {
+                name: name.to_string(),
+                mismatched: comparison.mismatched,
+                max_difference: comparison.max_difference,
+                diff_path,
+            });
+        }
+        Ok(())
+    }
+
+    fn save_actual(&self, name: &str, actual: &image::RgbaImage) -> Result<(), Error> {
+        let actual_path = self.dir.join(format!("{}.actual.png", name));
+        actual.save(Path::new(&actual_path))?;
+        Ok(())
+    }
+}
+
+/// Compares two images of the same size pixel by pixel.
+///
+/// A pixel is mismatched when any of its channels differ by more than `tolerance`.
+pub fn compare(
+    expected: &image::RgbaImage,
+    actual: &image::RgbaImage,
+    tolerance: u8,
+) -> Comparison {
+    assert_eq!(
+        expected.dimensions(),
+        actual.dimensions(),
+        "Cannot compare images of different sizes!"
+    );
+    let (width, height) = expected.dimensions();
+    let mut diff = image::RgbaImage::new(width, height);
+    let mut mismatched = 0;
+    let mut max_difference = 0;
+    for ((e, a), d) in expected
+        .pixels()
+        .zip(actual.pixels())
+        .zip(diff.pixels_mut())
+    {
+        let difference = e
+            .0
+            .iter()
+            .zip(a.0.iter())
+            .map(|(e, a)| (*e as i16 - *a as i16).abs() as u8)
+            .max()
+            .unwrap_or(0);
+        max_difference = max_difference.max(difference);
+        *d = if difference > tolerance {
+            mismatched += 1;
+            image::Rgba([255, 0, 0, 255])
+        } else {
+            let [r, g, b, _] = e.0;
+            image::Rgba([r / 4, g / 4, b / 4, 255])
+        };
+    }
+    Comparison {
+        mismatched,
+        max_difference,
+        diff,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(pixels: &[[u8; 4]]) -> image::RgbaImage {
+        let mut image = image::RgbaImage::new(pixels.len() as u32, 1);
+        for (p, pixel) in image.pixels_mut().zip(pixels) {
+            *p = image::Rgba(*pixel);
+        }
+        image
+    }
+
+    #[test]
+    fn compare_counts_pixels_over_tolerance() {
+        let expected = image(&[[0, 0, 0, 255], [100, 100, 100, 255], [200, 0, 0, 255]]);
+        let actual = image(&[[0, 0, 0, 255], [102, 100, 99, 255], [200, 0, 10, 255]]);
+
+        let comparison = compare(&expected, &actual, 2);
+        assert_eq!(comparison.mismatched, 1);
+        assert_eq!(comparison.max_difference, 10);
+        assert_eq!(comparison.diff.get_pixel(0, 0).0, [0, 0, 0, 255]);
+        assert_eq!(comparison.diff.get_pixel(1, 0).0, [25, 25, 25, 255]);
+        assert_eq!(comparison.diff.get_pixel(2, 0).0, [255, 0, 0, 255]);
+
+        let exact = compare(&expected, &actual, 0);
+        assert_eq!(exact.mismatched, 2);
+        assert_eq!(compare(&expected, &expected, 0).mismatched, 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn compare_rejects_different_sizes() {
+        compare(&image(&[[0; 4]]), &image(&[[0; 4], [0; 4]]), 0);
+    }
+}
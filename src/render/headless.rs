@@ -0,0 +1,57 @@
+use super::camera::Camera;
+use super::error::Error;
+use super::mesh::Canvas;
+use crate::render::renderer::Renderer;
+
+/// A [`Headless`] combines an offscreen render target with a graphics renderer.
+///
+/// It renders a [`Canvas`] without a window, which makes it usable on machines without a display
+/// e.g. for CI jobs or for batch rendering thumbnails from the same data that is shown on screen.
+///
+/// [`Headless`]: struct.Headless.html
+/// [`Canvas`]: ../mesh/struct.Canvas.html
+pub struct Headless {
+    renderer: Renderer,
+    width: u32,
+    height: u32,
+    pub canvas: Canvas,
+    /// The view of the canvas, changes are uploaded with the next frame.
+    pub camera: Camera,
+}
+
+/// [`Headless`]: struct.Headless.html
+impl Headless {
+    pub async fn new(instance: &wgpu::Instance, width: u32, height: u32) -> Result<Self, Error> {
+        let adapter = Renderer::request_adapter(instance, None).await?;
+        let (device, queue) = Renderer::request_device(&adapter).await?;
+        let renderer = Renderer::new_headless(device, queue, width, height);
+
+        let canvas = Canvas::new();
+        let camera = Camera::default();
+
+        Ok(Self {
+            renderer,
+            width,
+            height,
+            canvas,
+            camera,
+        })
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        log::info!("Resizing to {}x{}", width, height);
+        self.width = width;
+        self.height = height;
+        self.renderer.resize(width, height);
+    }
+
+    /// Renders the canvas and returns the resulting pixels.
+    pub fn render(&mut self) -> Result<image::RgbaImage, Error> {
+        self.renderer
+            .render_to_image(&mut self.canvas, &self.camera)
+    }
+}
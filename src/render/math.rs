@@ -23,6 +23,29 @@ pub type Quat = [f32; 4];
 /// 4x4 column matrix, each entry is a column
 pub type Mat4 = [[f32; 4]; 4];
 
+/// Axis aligned rectangle, `min` holds the smallest coordinates and `max` the largest.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Rect {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl Rect {
+    pub fn width(&self) -> f32 {
+        self.max[0] - self.min[0]
+    }
+
+    pub fn height(&self) -> f32 {
+        self.max[1] - self.min[1]
+    }
+}
+
+/// Returns the rotation by `angle` radians around the z axis, counter clockwise in 2D.
+pub fn z_rotation(angle: f32) -> Quat {
+    let (sin, cos) = (angle * 0.5).sin_cos();
+    [cos, 0.0, 0.0, sin]
+}
+
 pub trait RotationMatrix {
     fn to_rotation_matrix(self) -> cgmath::Matrix4<f32>;
 }
@@ -41,6 +64,15 @@ impl TranslationMatrix for Vec3 {
     }
 }
 
+pub trait ScaleMatrix {
+    fn to_scale_matrix(self) -> cgmath::Matrix4<f32>;
+}
+impl ScaleMatrix for Vec3 {
+    fn to_scale_matrix(self) -> cgmath::Matrix4<f32> {
+        cgmath::Matrix4::from_nonuniform_scale(self[0], self[1], self[2])
+    }
+}
+
 pub trait Identity {
     fn identity() -> Self;
 }
@@ -1,84 +1,1225 @@
+use super::atlas::{self, ShelfPacker};
+use super::error::Error;
+use super::framebuffer::Framebuffer;
 use super::math::*;
+use super::path::{self, FillRule, Path};
+use super::shape;
+use super::sprite::{Animation, Playback, SpriteSheet};
+use super::stroke::{self, StrokeStyle};
+use super::text::{self, Font, GlyphAtlas, GlyphId, TextBlock, TextStyle};
+use super::texture::{self, TexelRect, TextureHandle, TextureOptions};
+use std::collections::HashMap;
+use std::iter;
 use std::mem;
 use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
 
-fn quad_vertices(transform_index: u32, w: f32, h: f32) -> Vec<Vertex> {
+fn quad_vertices(w: f32, h: f32) -> Vec<Vertex> {
     let l = -w * 0.5;
     let r = w * 0.5;
     let t = h * 0.5;
     let b = -h * 0.5;
-    vertices(
-        transform_index,
-        &[[l, t, 0.0], [r, t, 0.0], [r, b, 0.0], [l, b, 0.0]],
-    )
+    let mut vertices = vertices(&[[l, t, 0.0], [r, t, 0.0], [r, b, 0.0], [l, b, 0.0]]);
+    // The whole texture is stretched over the quad, v points down the image
+    for (vertex, tex_coords) in
+        vertices
+            .iter_mut()
+            .zip(&[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]])
+    {
+        vertex.tex_coords = *tex_coords;
+    }
+    vertices
 }
-fn quad_indices(v0: usize) -> Vec<u32> {
-    let i0 = v0 as u32;
-    vec![i0, i0 + 2, i0 + 1, i0, i0 + 3, i0 + 2]
+/// Default tessellation tolerance, small enough to look smooth in the default -1..1 view.
+const DEFAULT_TOLERANCE: f32 = 0.001;
+
+const QUAD_INDICES: [u32; 6] = [0, 2, 1, 0, 3, 2];
+/// Color of vertices that were not colored.
+const WHITE: Rgba = [1.0, 1.0, 1.0, 1.0];
+
+/// Number of separate ranges of a canvas buffer that are uploaded, more are merged.
+const MAX_DIRTY_RANGES: usize = 8;
+
+static NEXT_CANVAS_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Handle to a mesh stored in a [`Canvas`].
+///
+/// Handles stay valid when other meshes are deleted from the canvas.
+/// Once the mesh itself is deleted the handle is stale and using it returns an error,
+/// even if the canvas reuses the slot for a new mesh. Using it with another canvas
+/// returns an error too.
+///
+/// [`Canvas`]: struct.Canvas.html
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Mesh {
+    canvas: u64,
+    index: u32,
+    generation: u32,
 }
 
+/// Location of a mesh's data in the canvas buffers.
 #[derive(Clone, Debug)]
-pub struct Mesh {
-    pub vertex_range: Range<usize>,
-    pub index_range: Range<usize>,
-    pub transform_index: usize,
+struct MeshData {
+    vertex_range: Range<usize>,
+    index_range: Range<usize>,
+    transform_index: usize,
+    texture: TextureHandle,
+    /// Region of the drawn texture that texture coordinates from 0 to 1 are mapped to.
+    region: Rect,
+    /// Texture coordinates of the vertices before they were mapped to the region,
+    /// so retexturing maps them once instead of undoing the previous mapping.
+    tex_coords: Vec<Vec2>,
+}
+
+/// A run of indices, up to the start of the next batch, that are drawn with the same texture.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Batch {
+    start: usize,
+    texture: TextureHandle,
+}
+
+/// A texture added to the canvas.
+enum TextureSource {
+    /// An image with a texture of its own.
+    ///
+    /// The renderer creates the texture whenever its version changes and otherwise writes
+    /// the dirty part of the image into the existing texture.
+    Image {
+        image: image::RgbaImage,
+        version: u64,
+        options: TextureOptions,
+        /// Part of the image changed since the renderer last uploaded it.
+        dirty: Option<TexelRect>,
+    },
+    /// An image packed into an atlas page, the page is a texture of the canvas itself.
+    ///
+    /// Meshes that use the texture are drawn with the page and their texture coordinates
+    /// are mapped into the region, so sprites from the same page are drawn together.
+    Packed {
+        page: TextureHandle,
+        region: Rect,
+        /// Position of the image in the page.
+        texels: TexelRect,
+    },
+}
+
+/// The image of a texture that the renderer keeps on the GPU.
+pub(crate) struct TextureImage<'a> {
+    pub handle: TextureHandle,
+    pub image: &'a image::RgbaImage,
+    /// Changes when the texture has to be created again rather than updated.
+    pub version: u64,
+    /// Part of the image changed since the last upload.
+    pub dirty: Option<TexelRect>,
+    pub options: TextureOptions,
+}
+
+/// Ranges of the canvas buffers that changed since the renderer last uploaded them,
+/// sorted and apart from each other.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct DirtyRanges {
+    pub vertices: Vec<Range<usize>>,
+    pub indices: Vec<Range<usize>>,
+    pub transforms: Vec<Range<usize>>,
+}
+
+/// An atlas page that small textures are packed into.
+#[derive(Debug)]
+struct Page {
+    texture: TextureHandle,
+    packer: ShelfPacker,
+    /// Only textures with the same options are packed into a page as they share its sampler.
+    options: TextureOptions,
+}
+
+#[derive(Debug)]
+struct Slot {
+    generation: u32,
+    data: Option<MeshData>,
 }
 
 #[derive(Debug)]
 pub struct Canvas {
-    pub meshes: Vec<Mesh>,
-    pub vertices: Vec<Vertex>,
-    pub indices: Vec<u32>,
-    pub transforms: Vec<Transform>,
+    /// Unique across canvases, so a renderer notices when it is given a different canvas.
+    id: u64,
+    slots: Vec<Slot>,
+    free_slots: Vec<u32>,
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+    transforms: Vec<Transform>,
+    /// Slot of the mesh that owns each transform, `None` once the mesh is deleted.
+    /// Meshes are in the order they are drawn, so compaction never visits free slots or sorts.
+    owners: Vec<Option<u32>>,
+    /// Number of vertices, indices and transforms that belong to deleted meshes.
+    garbage_vertices: usize,
+    garbage_indices: usize,
+    garbage_transforms: usize,
+    /// Maximum distance between a curve and its tessellation.
+    tolerance: f32,
+    glyphs: GlyphAtlas,
+    /// Textures added to the canvas, the first has the handle index `TextureHandle::FIRST`.
+    textures: Vec<TextureSource>,
+    pages: Vec<Page>,
+    /// Consecutive meshes with the same texture are drawn together, in the order they were added.
+    batches: Vec<Batch>,
+    /// Sprite animations playing on meshes.
+    animations: HashMap<Mesh, Playback>,
+    framebuffer: Option<Framebuffer>,
+    dirty: DirtyRanges,
 }
 
 impl Canvas {
     pub fn new() -> Self {
         Self {
-            meshes: Vec::new(),
+            id: NEXT_CANVAS_ID.fetch_add(1, Ordering::Relaxed),
+            slots: Vec::new(),
+            free_slots: Vec::new(),
             vertices: Vec::new(),
             indices: Vec::new(),
             transforms: Vec::new(),
+            owners: Vec::new(),
+            garbage_vertices: 0,
+            garbage_indices: 0,
+            garbage_transforms: 0,
+            tolerance: DEFAULT_TOLERANCE,
+            glyphs: GlyphAtlas::new(),
+            textures: Vec::new(),
+            pages: Vec::new(),
+            batches: Vec::new(),
+            animations: HashMap::new(),
+            framebuffer: None,
+            dirty: DirtyRanges::default(),
+        }
+    }
+
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    pub fn transforms(&self) -> &[Transform] {
+        &self.transforms
+    }
+
+    /// Returns the images of the textures that meshes can use,
+    /// with the changes to their contents and how they are sampled.
+    pub(crate) fn textures(&self) -> impl Iterator<Item = TextureImage<'_>> + '_ {
+        let glyphs = self.glyphs.image().map(|image| TextureImage {
+            handle: TextureHandle::GLYPHS,
+            image,
+            version: self.glyphs.version(),
+            dirty: self.glyphs.dirty(),
+            options: TextureOptions::linear(),
+        });
+        let canvas = self.id;
+        let textures =
+            self.textures
+                .iter()
+                .enumerate()
+                .filter_map(move |(i, source)| match source {
+                    TextureSource::Image {
+                        image,
+                        version,
+                        options,
+                        dirty,
+                    } => Some(TextureImage {
+                        handle: TextureHandle::new(canvas, TextureHandle::FIRST + i as u32),
+                        image,
+                        version: *version,
+                        dirty: *dirty,
+                        options: *options,
+                    }),
+                    TextureSource::Packed { .. } => None,
+                });
+        glyphs.into_iter().chain(textures)
+    }
+
+    /// Returns the ranges of vertices, indices and transforms that changed since the last upload.
+    pub(crate) fn dirty_ranges(&self) -> &DirtyRanges {
+        &self.dirty
+    }
+
+    /// Marks the buffers and the contents of all textures as uploaded.
+    pub(crate) fn clear_updates(&mut self) {
+        self.dirty = DirtyRanges::default();
+        self.glyphs.clear_dirty();
+        if let Some(framebuffer) = &mut self.framebuffer {
+            framebuffer.clear_dirty();
         }
+        for source in &mut self.textures {
+            if let TextureSource::Image { dirty, .. } = source {
+                *dirty = None;
+            }
+        }
+    }
+
+    /// Shows the framebuffer behind all meshes, scaled to fill the render target,
+    /// or removes the framebuffer that is shown if `None`.
+    pub fn set_framebuffer(&mut self, framebuffer: Option<Framebuffer>) {
+        self.framebuffer = framebuffer;
+    }
+
+    pub fn framebuffer(&self) -> Option<&Framebuffer> {
+        self.framebuffer.as_ref()
+    }
+
+    /// Gives access to the pixels of the framebuffer that is shown, if any.
+    pub fn framebuffer_mut(&mut self) -> Option<&mut Framebuffer> {
+        self.framebuffer.as_mut()
+    }
+
+    /// Returns the ranges of indices to draw with each texture, in draw order.
+    pub(crate) fn batches(&self) -> impl Iterator<Item = (Range<u32>, TextureHandle)> + '_ {
+        let ends = self
+            .batches
+            .iter()
+            .skip(1)
+            .map(|batch| batch.start)
+            .chain(iter::once(self.indices.len()));
+        self.batches
+            .iter()
+            .zip(ends)
+            .map(|(batch, end)| (batch.start as u32..end as u32, batch.texture))
+    }
+
+    /// Returns true if the mesh has not been deleted from this canvas.
+    pub fn contains(&self, mesh: &Mesh) -> bool {
+        self.data(mesh).is_ok()
+    }
+
+    /// Returns the handles of all meshes in the canvas.
+    pub fn meshes(&self) -> impl Iterator<Item = Mesh> + '_ {
+        let canvas = self.id;
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(move |(index, slot)| {
+                slot.data.as_ref().map(|_| Mesh {
+                    canvas,
+                    index: index as u32,
+                    generation: slot.generation,
+                })
+            })
     }
 
-    pub fn color(&mut self, mesh: &Mesh, color: Rgba) {
-        for v in &mut self.vertices[mesh.vertex_range.clone()] {
+    pub fn color(&mut self, mesh: &Mesh, color: Rgba) -> Result<(), Error> {
+        let vertex_range = self.data(mesh)?.vertex_range.clone();
+        for v in &mut self.vertices[vertex_range.clone()] {
             v.color = color;
         }
+        mark_range(&mut self.dirty.vertices, vertex_range);
+        Ok(())
+    }
+
+    /// Returns the position, rotation, scale and pivot of the mesh.
+    pub fn transform(&self, mesh: &Mesh) -> Result<Transform, Error> {
+        let transform_index = self.data(mesh)?.transform_index;
+        Ok(self.transforms[transform_index])
+    }
+
+    /// Replaces the position, rotation, scale and pivot of the mesh.
+    ///
+    /// Only the transform is uploaded again, so meshes can be moved every frame
+    /// without touching their vertices.
+    pub fn set_transform(&mut self, mesh: &Mesh, transform: Transform) -> Result<(), Error> {
+        self.update_transform(mesh, |t| *t = transform)
+    }
+
+    /// Moves the mesh's local origin to the position.
+    pub fn set_translation(&mut self, mesh: &Mesh, translation: Vec3) -> Result<(), Error> {
+        self.update_transform(mesh, |t| t.translation = translation)
+    }
+
+    /// Moves the mesh by the offset from where it is.
+    pub fn translate(&mut self, mesh: &Mesh, offset: Vec3) -> Result<(), Error> {
+        self.update_transform(mesh, |t| {
+            for (a, b) in t.translation.iter_mut().zip(&offset) {
+                *a += b;
+            }
+        })
+    }
+
+    /// Sets the rotation of the mesh around its pivot.
+    pub fn set_rotation(&mut self, mesh: &Mesh, rotation: Quat) -> Result<(), Error> {
+        self.update_transform(mesh, |t| t.rotation = rotation)
+    }
+
+    /// Rotates the mesh `angle` radians counter clockwise around its pivot in the xy plane,
+    /// replacing any previous rotation.
+    pub fn set_angle(&mut self, mesh: &Mesh, angle: f32) -> Result<(), Error> {
+        self.set_rotation(mesh, z_rotation(angle))
+    }
+
+    /// Scales the mesh along each axis around its pivot.
+    pub fn set_scale(&mut self, mesh: &Mesh, scale: Vec3) -> Result<(), Error> {
+        self.update_transform(mesh, |t| t.scale = scale)
+    }
+
+    /// Scales the mesh by the same factor along every axis around its pivot.
+    pub fn set_uniform_scale(&mut self, mesh: &Mesh, scale: f32) -> Result<(), Error> {
+        self.set_scale(mesh, [scale, scale, scale])
+    }
+
+    /// Sets the point in the mesh's local space that it is rotated and scaled around,
+    /// the local origin by default.
+    ///
+    /// Moving the pivot does not move the mesh unless it is rotated or scaled.
+    pub fn set_pivot(&mut self, mesh: &Mesh, pivot: Vec3) -> Result<(), Error> {
+        self.update_transform(mesh, |t| t.pivot = pivot)
+    }
+
+    /// Adds an image that meshes can be textured with, sampled according to the options.
+    ///
+    /// Small images that don't wrap are packed into shared atlas pages so meshes using
+    /// different images can still be drawn together, other images get a texture of their own.
+    /// Nothing is added if the image is empty or too large for a texture,
+    /// or if the options are invalid.
+    pub fn add_texture(
+        &mut self,
+        image: image::RgbaImage,
+        options: TextureOptions,
+    ) -> Result<TextureHandle, Error> {
+        texture::check_size(&image)?;
+        texture::check_options(&options)?;
+        let (width, height) = image.dimensions();
+        let packable = options.packable()
+            && width <= atlas::MAX_PACKED_SIZE
+            && height <= atlas::MAX_PACKED_SIZE;
+        let source = if packable {
+            self.pack(&image, options)
+        } else {
+            TextureSource::Image {
+                image,
+                version: texture::next_version(),
+                options,
+                dirty: None,
+            }
+        };
+        Ok(self.add_source(source))
+    }
+
+    /// Replaces the pixels of the texture in the rectangle with its top left corner at (x, y)
+    /// by the image, an image the size of the texture replaces all of it.
+    ///
+    /// Only the changed rectangle is written to the existing GPU texture with the next frame,
+    /// so images that change every frame, like video or simulation output, can be streamed in.
+    /// Mipmapped textures are created again to regenerate their smaller levels.
+    pub fn update_texture(
+        &mut self,
+        texture: TextureHandle,
+        x: u32,
+        y: u32,
+        image: &image::RgbaImage,
+    ) -> Result<(), Error> {
+        let source = self.source(texture)?;
+        let (target, origin) = match source {
+            TextureSource::Image { image, .. } => (texture, TexelRect::of(image)),
+            TextureSource::Packed { page, texels, .. } => (*page, *texels),
+        };
+        let (width, height) = image.dimensions();
+        // Rectangles reaching past u32::MAX don't fit either
+        let fits = |start: u32, length: u32, size: u32| {
+            start.checked_add(length).map_or(false, |end| end <= size)
+        };
+        if !fits(x, width, origin.width) || !fits(y, height, origin.height) {
+            return Err(Error::UpdateOutOfBounds {
+                x,
+                y,
+                width,
+                height,
+                size: (origin.width, origin.height),
+            });
+        }
+        match &mut self.textures[target.index() - TextureHandle::FIRST as usize] {
+            TextureSource::Image {
+                image: target_image,
+                version,
+                options,
+                dirty,
+            } => {
+                image::imageops::replace(target_image, image, origin.x + x, origin.y + y);
+                let changed = if target == texture {
+                    TexelRect {
+                        x,
+                        y,
+                        width,
+                        height,
+                    }
+                } else {
+                    atlas::extrude(target_image, origin)
+                };
+                if options.mipmaps {
+                    *version = texture::next_version();
+                } else {
+                    mark_dirty(dirty, changed);
+                }
+            }
+            TextureSource::Packed { .. } => unreachable!("Atlas pages are never packed"),
+        }
+        Ok(())
+    }
+
+    /// Decodes the contents of an image file, such as a PNG, and adds it as a texture.
+    ///
+    /// Any format and color type that can be decoded is converted to 8-bit RGBA.
+    pub fn load_texture(
+        &mut self,
+        bytes: &[u8],
+        options: TextureOptions,
+    ) -> Result<TextureHandle, Error> {
+        self.add_texture(texture::decode(bytes)?, options)
+    }
+
+    /// Opens the image file at the path and adds it as a texture.
+    pub fn open_texture<P: AsRef<std::path::Path>>(
+        &mut self,
+        path: P,
+        options: TextureOptions,
+    ) -> Result<TextureHandle, Error> {
+        self.add_texture(texture::open(path)?, options)
+    }
+
+    /// Returns true if the texture can be used by meshes in this canvas.
+    pub fn contains_texture(&self, texture: TextureHandle) -> bool {
+        texture.index() < TextureHandle::FIRST as usize || self.source(texture).is_ok()
+    }
+
+    /// Textures the mesh with the image, which is multiplied by the vertex colors.
+    ///
+    /// Meshes are white until they are colored, so the image shows unchanged by default.
+    ///
+    /// The image is mapped onto the mesh with the texture coordinates of its vertices,
+    /// a quad shows the whole image. Setting [`TextureHandle::WHITE`] removes the texture.
+    ///
+    /// [`TextureHandle::WHITE`]: ../texture/struct.TextureHandle.html#associatedconstant.WHITE
+    pub fn texture(&mut self, mesh: &Mesh, texture: TextureHandle) -> Result<(), Error> {
+        if !self.contains_texture(texture) {
+            return Err(Error::TextureNotFound(texture));
+        }
+        self.data(mesh)?;
+        self.animations.remove(mesh);
+        let (_, region) = resolve(&self.textures, texture);
+        self.set_texture(mesh, texture, region);
+        Ok(())
+    }
+
+    /// Creates a sprite sheet without frames for the texture, which must have been added
+    /// to this canvas.
+    pub fn sprite_sheet(&self, texture: TextureHandle) -> Result<SpriteSheet, Error> {
+        let source = self.source(texture)?;
+        let (width, height) = match source {
+            TextureSource::Image { image, .. } => image.dimensions(),
+            TextureSource::Packed { texels, .. } => (texels.width, texels.height),
+        };
+        Ok(SpriteSheet::new(texture, width, height))
+    }
+
+    /// Shows a frame of the sprite sheet on the mesh, stopping any animation playing on it.
+    ///
+    /// The mesh is textured with the sheet and the frame is mapped onto it with the texture
+    /// coordinates of its vertices, a quad shows the whole frame.
+    pub fn sprite(&mut self, mesh: &Mesh, sheet: &SpriteSheet, frame: usize) -> Result<(), Error> {
+        let region = self.frame_region(sheet, frame)?;
+        self.data(mesh)?;
+        self.animations.remove(mesh);
+        self.set_texture(mesh, sheet.texture(), region);
+        Ok(())
+    }
+
+    /// Plays the animation on the mesh starting from its first frame,
+    /// the frames are changed as time passes with [`advance`](#method.advance).
+    pub fn animate(
+        &mut self,
+        mesh: &Mesh,
+        sheet: &SpriteSheet,
+        animation: &Animation,
+    ) -> Result<(), Error> {
+        if animation.frames.is_empty() {
+            return Err(Error::EmptyAnimation);
+        }
+        let regions = animation
+            .frames
+            .iter()
+            .map(|frame| self.frame_region(sheet, *frame))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.data(mesh)?;
+        self.set_texture(mesh, sheet.texture(), regions[0]);
+        self.animations.insert(
+            *mesh,
+            Playback {
+                regions,
+                fps: animation.fps,
+                looping: animation.looping,
+                time: 0.0,
+                current: 0,
+            },
+        );
+        Ok(())
+    }
+
+    /// Stops the animation playing on the mesh, leaving it on its current frame.
+    ///
+    /// Returns false if no animation was playing.
+    pub fn stop_animation(&mut self, mesh: &Mesh) -> bool {
+        self.animations.remove(mesh).is_some()
+    }
+
+    /// Advances the animations playing on meshes by `dt` seconds.
+    pub fn advance(&mut self, dt: f32) {
+        let changed = self
+            .animations
+            .iter_mut()
+            .filter_map(|(mesh, playback)| {
+                let frame = playback.advance(dt)?;
+                Some((*mesh, playback.regions[frame]))
+            })
+            .collect::<Vec<_>>();
+        for (mesh, region) in changed {
+            if let Ok(data) = self.data(&mesh) {
+                let texture = data.texture;
+                self.set_texture(&mesh, texture, region);
+            }
+        }
     }
 
     pub fn quad(&mut self, x: f32, y: f32, w: f32, h: f32) -> Mesh {
+        self.push(
+            quad_vertices(w, h),
+            &QUAD_INDICES,
+            transform(x, y, 0.0),
+            TextureHandle::WHITE,
+        )
+    }
+
+    /// Adds the custom geometry described by the builder to the canvas.
+    ///
+    /// The geometry is validated first, nothing is added to the canvas if it is invalid.
+    pub fn mesh(&mut self, builder: MeshBuilder) -> Result<Mesh, Error> {
+        let num_vertices = builder.positions.len();
+        if builder.indices.len() % 3 != 0 {
+            return Err(Error::IndexCount(builder.indices.len()));
+        }
+        if let Some(index) = builder
+            .indices
+            .iter()
+            .find(|index| **index as usize >= num_vertices)
+        {
+            return Err(Error::IndexOutOfBounds {
+                index: *index,
+                len: num_vertices,
+            });
+        }
+        if let Some(colors) = &builder.colors {
+            check_attribute_count("colors", colors.len(), num_vertices)?;
+        }
+        if let Some(tex_coords) = &builder.tex_coords {
+            check_attribute_count("tex_coords", tex_coords.len(), num_vertices)?;
+        }
+        if !self.contains_texture(builder.texture) {
+            return Err(Error::TextureNotFound(builder.texture));
+        }
+
+        Ok(self.build(builder))
+    }
+
+    /// Sets the maximum distance between a curve and the straight segments that approximate it
+    /// for shapes that are added afterwards.
+    pub fn set_tolerance(&mut self, tolerance: f32) {
+        self.tolerance = tolerance;
+    }
+
+    pub fn tolerance(&self) -> f32 {
+        self.tolerance
+    }
+
+    pub fn circle(&mut self, x: f32, y: f32, radius: f32) -> Mesh {
+        self.shape(x, y, shape::circle(radius, self.tolerance))
+    }
+
+    pub fn ellipse(&mut self, x: f32, y: f32, rx: f32, ry: f32) -> Mesh {
+        self.shape(x, y, shape::ellipse(rx, ry, self.tolerance))
+    }
+
+    /// Adds a ring segment centered on (x, y), angles are in radians counter-clockwise from +x.
+    pub fn arc(&mut self, x: f32, y: f32, inner: f32, outer: f32, start: f32, end: f32) -> Mesh {
+        self.shape(x, y, shape::arc(inner, outer, start, end, self.tolerance))
+    }
+
+    /// Adds a pie slice centered on (x, y), angles are in radians counter-clockwise from +x.
+    pub fn pie(&mut self, x: f32, y: f32, radius: f32, start: f32, end: f32) -> Mesh {
+        self.shape(x, y, shape::pie(radius, start, end, self.tolerance))
+    }
+
+    pub fn rounded_rect(&mut self, x: f32, y: f32, w: f32, h: f32, radius: f32) -> Mesh {
+        self.shape(x, y, shape::rounded_rect(w, h, radius, self.tolerance))
+    }
+
+    /// Adds a simple polygon, which may be concave, with its points relative to (x, y).
+    pub fn polygon(&mut self, x: f32, y: f32, points: &[Vec2]) -> Result<Mesh, Error> {
+        Ok(self.shape(x, y, shape::polygon(points)?))
+    }
+
+    /// Adds a stroked straight line between the points.
+    pub fn line(&mut self, from: Vec2, to: Vec2, style: &StrokeStyle) -> Mesh {
+        self.polyline(&[from, to], false, style)
+    }
+
+    /// Adds a stroke through the points as a single mesh,
+    /// closed strokes also join the last point back to the first.
+    pub fn polyline(&mut self, points: &[Vec2], closed: bool, style: &StrokeStyle) -> Mesh {
+        let builder = stroke::polyline(points, closed, style, self.tolerance);
+        self.build(builder)
+    }
+
+    /// Fills the area enclosed by the path, open subpaths are closed implicitly.
+    pub fn fill_path(&mut self, path: &Path, rule: FillRule) -> Mesh {
+        let builder = path::fill(&path.flatten(self.tolerance), rule);
+        self.build(builder)
+    }
+
+    /// Strokes the outline of the path as a single mesh.
+    pub fn stroke_path(&mut self, path: &Path, style: &StrokeStyle) -> Mesh {
+        let builder = path::stroke(&path.flatten(self.tolerance), style, self.tolerance);
+        self.build(builder)
+    }
+
+    /// Adds the text with the baseline of its first line starting at (x, y) as a single mesh.
+    ///
+    /// `size` is the height of an em in canvas units, characters are laid out in equally wide
+    /// cells and `\n` starts a new line. The text is white until it is colored.
+    pub fn text(&mut self, font: &Font, text: &str, x: f32, y: f32, size: f32) -> Mesh {
+        let glyphs = text::layout_monospaced(font, text);
+        self.glyphs(font, &glyphs, x, y, size)
+    }
+
+    /// Adds a block of proportional text with the baseline of its first line starting at (x, y)
+    /// as a single mesh.
+    ///
+    /// The text is kerned, wrapped and aligned according to the style and is white until it is colored.
+    pub fn text_block(
+        &mut self,
+        font: &Font,
+        text: &str,
+        x: f32,
+        y: f32,
+        style: &TextStyle,
+    ) -> TextBlock {
+        let layout = text::layout(font, text, style);
+        let mesh = self.glyphs(font, &layout.glyphs, x, y, style.size);
+        let bounds = layout.bounds(font, style.size);
+        TextBlock {
+            mesh,
+            bounds: Rect {
+                min: [x + bounds.min[0], y + bounds.min[1]],
+                max: [x + bounds.max[0], y + bounds.max[1]],
+            },
+        }
+    }
+
+    /// Adds textured quads for the laid out glyphs, `size` is the height of an em in canvas units.
+    fn glyphs(
+        &mut self,
+        font: &Font,
+        glyphs: &[(GlyphId, Vec2)],
+        x: f32,
+        y: f32,
+        size: f32,
+    ) -> Mesh {
+        let scale = size / font.pixel_size();
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for (id, [gx, gy]) in glyphs {
+            let glyph = match self.glyphs.glyph(font, *id) {
+                Some(glyph) => glyph,
+                None => continue,
+            };
+            // Glyph metrics are in pixels with y down
+            let l = (gx + glyph.offset[0]) * scale;
+            let t = -(gy + glyph.offset[1]) * scale;
+            let r = l + glyph.size[0] * scale;
+            let b = t - glyph.size[1] * scale;
+            let [u0, v0] = glyph.tex_min;
+            let [u1, v1] = glyph.tex_max;
+            let first = vertices.len() as u32;
+            for (position, tex_coords) in &[
+                ([l, t, 0.0], [u0, v0]),
+                ([r, t, 0.0], [u1, v0]),
+                ([r, b, 0.0], [u1, v1]),
+                ([l, b, 0.0], [u0, v1]),
+            ] {
+                vertices.push(Vertex {
+                    position: *position,
+                    color: WHITE,
+                    tex_coords: *tex_coords,
+                    mix_factor: 1.0,
+                    ..Default::default()
+                });
+            }
+            indices.extend(QUAD_INDICES.iter().map(|index| index + first));
+        }
+        self.push(
+            vertices,
+            &indices,
+            transform(x, y, 0.0),
+            TextureHandle::GLYPHS,
+        )
+    }
+
+    fn shape(&mut self, x: f32, y: f32, builder: MeshBuilder) -> Mesh {
+        self.build(builder.with_translation([x, y, 0.0]))
+    }
+
+    /// Adds the geometry without validating it.
+    fn build(&mut self, builder: MeshBuilder) -> Mesh {
+        let num_vertices = builder.positions.len();
+        let vertices = (0..num_vertices)
+            .map(|i| Vertex {
+                position: builder.positions[i],
+                color: builder
+                    .colors
+                    .as_ref()
+                    .map_or(builder.color, |colors| colors[i]),
+                tex_coords: builder
+                    .tex_coords
+                    .as_ref()
+                    .map_or([0.0, 0.0], |tex_coords| tex_coords[i]),
+                mix_factor: builder.mix_factor,
+                ..Default::default()
+            })
+            .collect();
+        self.push(
+            vertices,
+            &builder.indices,
+            builder.transform,
+            builder.texture,
+        )
+    }
+
+    /// Deletes the mesh from the canvas.
+    ///
+    /// The mesh's indices are collapsed into degenerate triangles so it is no longer drawn,
+    /// its vertices and transform are reclaimed later by [`compact`](#method.compact).
+    /// This makes deletion proportional to the size of the deleted mesh rather than the canvas.
+    pub fn delete(&mut self, mesh: &Mesh) -> Result<(), Error> {
+        let data = self.data(mesh)?.clone();
+        self.slots[mesh.index as usize].data = None;
+        self.free_slots.push(mesh.index);
+        self.owners[data.transform_index] = None;
+        self.animations.remove(mesh);
+
+        let degenerate = data.vertex_range.start as u32;
+        for index in &mut self.indices[data.index_range.clone()] {
+            *index = degenerate;
+        }
+        mark_range(&mut self.dirty.indices, data.index_range.clone());
+        self.garbage_vertices += data.vertex_range.len();
+        self.garbage_indices += data.index_range.len();
+        self.garbage_transforms += 1;
+
+        // Compact once most of the canvas is garbage, this keeps the cost of compaction
+        // proportional to the number of deleted meshes.
+        if self.garbage_vertices * 2 > self.vertices.len() {
+            self.compact();
+        }
+        Ok(())
+    }
+
+    /// Removes the data of deleted meshes from the canvas buffers.
+    ///
+    /// The handles of the remaining meshes stay valid.
+    /// Takes time proportional to the size of the buffers, deleted meshes included.
+    pub fn compact(&mut self) {
+        if self.garbage_vertices == 0 && self.garbage_indices == 0 && self.garbage_transforms == 0 {
+            return;
+        }
+        let owners = mem::take(&mut self.owners)
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+
+        let mut vertices = Vec::with_capacity(self.vertices.len() - self.garbage_vertices);
+        let mut indices = Vec::with_capacity(self.indices.len() - self.garbage_indices);
+        let mut transforms = Vec::with_capacity(self.transforms.len() - self.garbage_transforms);
+        let mut batches: Vec<Batch> = Vec::new();
+        for &owner in &owners {
+            let data = self.slots[owner as usize]
+                .data
+                .as_mut()
+                .expect("Transform owner does not exist!");
+            let v0 = vertices.len();
+            let i0 = indices.len();
+            let transform_index = transforms.len();
+            let old_v0 = data.vertex_range.start as u32;
+
+            vertices.extend(
+                self.vertices[data.vertex_range.clone()]
+                    .iter()
+                    .map(|vertex| Vertex {
+                        transform_index: transform_index as u32,
+                        ..*vertex
+                    }),
+            );
+            indices.extend(
+                self.indices[data.index_range.clone()]
+                    .iter()
+                    .map(|index| index - old_v0 + v0 as u32),
+            );
+            transforms.push(self.transforms[data.transform_index]);
+            let (texture, _) = resolve(&self.textures, data.texture);
+            if i0 < indices.len() && batches.last().map(|batch| batch.texture) != Some(texture) {
+                batches.push(Batch { start: i0, texture });
+            }
+
+            data.vertex_range = v0..vertices.len();
+            data.index_range = i0..indices.len();
+            data.transform_index = transform_index;
+        }
+        self.vertices = vertices;
+        self.indices = indices;
+        self.transforms = transforms;
+        self.owners = owners.into_iter().map(Some).collect();
+        self.batches = batches;
+        // Everything moved, the renderer has to upload the whole buffers again
+        self.dirty = DirtyRanges {
+            vertices: vec![0..self.vertices.len()],
+            indices: vec![0..self.indices.len()],
+            transforms: vec![0..self.transforms.len()],
+        };
+        self.garbage_vertices = 0;
+        self.garbage_indices = 0;
+        self.garbage_transforms = 0;
+    }
+
+    /// Appends the vertices and indices of a new mesh with its own transform.
+    ///
+    /// The indices are local to the vertices of the mesh,
+    /// its texture coordinates are mapped to the texture's region here.
+    fn push(
+        &mut self,
+        mut vertices: Vec<Vertex>,
+        indices: &[u32],
+        transform: Transform,
+        texture: TextureHandle,
+    ) -> Mesh {
         let v0 = self.vertices.len();
         let i0 = self.indices.len();
-        let layout = Mesh {
-            vertex_range: v0..v0 + 4,
-            index_range: i0..i0 + 6,
+        let (page, region) = resolve(&self.textures, texture);
+        let data = MeshData {
+            vertex_range: v0..v0 + vertices.len(),
+            index_range: i0..i0 + indices.len(),
             transform_index: self.transforms.len(),
+            texture,
+            region,
+            tex_coords: vertices.iter().map(|vertex| vertex.tex_coords).collect(),
+        };
+        if !indices.is_empty() && self.batches.last().map(|batch| batch.texture) != Some(page) {
+            self.batches.push(Batch {
+                start: i0,
+                texture: page,
+            });
+        }
+
+        for vertex in &mut vertices {
+            vertex.transform_index = data.transform_index as u32;
+            vertex.tex_coords = atlas::to_region(&region, vertex.tex_coords);
+        }
+        self.vertices.extend(vertices);
+        self.indices
+            .extend(indices.iter().map(|index| index + v0 as u32));
+        self.transforms.push(transform);
+        mark_range(&mut self.dirty.vertices, data.vertex_range.clone());
+        mark_range(&mut self.dirty.indices, data.index_range.clone());
+        mark_range(
+            &mut self.dirty.transforms,
+            data.transform_index..data.transform_index + 1,
+        );
+        let mesh = self.insert(data);
+        self.owners.push(Some(mesh.index));
+        mesh
+    }
+
+    /// Draws the mesh with the texture, mapping its texture coordinates to the region.
+    /// The mesh must exist.
+    fn set_texture(&mut self, mesh: &Mesh, texture: TextureHandle, region: Rect) {
+        let data = self.slots[mesh.index as usize]
+            .data
+            .as_mut()
+            .expect("Textured mesh does not exist!");
+        data.region = region;
+        data.texture = texture;
+        let vertex_range = data.vertex_range.clone();
+        let index_range = data.index_range.clone();
+        for (v, tex_coords) in self.vertices[vertex_range.clone()]
+            .iter_mut()
+            .zip(&data.tex_coords)
+        {
+            v.tex_coords = atlas::to_region(&region, *tex_coords);
+            v.mix_factor = 1.0;
+        }
+        mark_range(&mut self.dirty.vertices, vertex_range);
+        let (page, _) = resolve(&self.textures, texture);
+        self.batch(index_range, page);
+    }
+
+    /// Returns the region of the sheet's texture covered by the frame.
+    fn frame_region(&self, sheet: &SpriteSheet, frame: usize) -> Result<Rect, Error> {
+        if !self.contains_texture(sheet.texture()) {
+            return Err(Error::TextureNotFound(sheet.texture()));
+        }
+        let frame_region = sheet.region(frame)?;
+        let (_, region) = resolve(&self.textures, sheet.texture());
+        Ok(atlas::subregion(&region, &frame_region))
+    }
+
+    fn add_source(&mut self, source: TextureSource) -> TextureHandle {
+        let handle = TextureHandle::new(self.id, TextureHandle::FIRST + self.textures.len() as u32);
+        self.textures.push(source);
+        handle
+    }
+
+    /// Packs the image into the first atlas page with the same options and space for it,
+    /// starting a new page if needed.
+    fn pack(&mut self, image: &image::RgbaImage, options: TextureOptions) -> TextureSource {
+        let (width, height) = image.dimensions();
+        let packed = self
+            .pages
+            .iter_mut()
+            .filter(|page| page.options == options)
+            .find_map(|page| {
+                let position = page.packer.pack(width, height)?;
+                Some((page.texture, position))
+            });
+        let (page, (x, y)) = match packed {
+            Some(packed) => packed,
+            None => {
+                let mut packer =
+                    ShelfPacker::new(atlas::PAGE_SIZE, atlas::PAGE_SIZE, atlas::PAGE_PADDING);
+                let position = packer
+                    .pack(width, height)
+                    .expect("Packed textures always fit in an empty page!");
+                let texture = self.add_source(TextureSource::Image {
+                    image: image::RgbaImage::new(atlas::PAGE_SIZE, atlas::PAGE_SIZE),
+                    version: texture::next_version(),
+                    options,
+                    dirty: None,
+                });
+                self.pages.push(Page {
+                    texture,
+                    packer,
+                    options,
+                });
+                (texture, position)
+            }
+        };
+        match &mut self.textures[page.index() - TextureHandle::FIRST as usize] {
+            TextureSource::Image {
+                image: page_image,
+                dirty,
+                ..
+            } => {
+                mark_dirty(dirty, atlas::blit(page_image, image, x, y));
+                TextureSource::Packed {
+                    page,
+                    region: atlas::region(page_image, x, y, width, height),
+                    texels: TexelRect {
+                        x,
+                        y,
+                        width,
+                        height,
+                    },
+                }
+            }
+            TextureSource::Packed { .. } => unreachable!("Atlas pages are never packed"),
+        }
+    }
+
+    /// Draws the indices, which belong to a single mesh, with the texture.
+    ///
+    /// The batch containing the indices is split around them and merged with its neighbours
+    /// if they share the texture.
+    fn batch(&mut self, range: Range<usize>, texture: TextureHandle) {
+        if range.start == range.end {
+            return;
+        }
+        let i = match self
+            .batches
+            .binary_search_by_key(&range.start, |batch| batch.start)
+        {
+            Ok(i) => i,
+            Err(i) => i - 1,
         };
+        let batch = self.batches[i];
+        if batch.texture == texture {
+            return;
+        }
+        let end = self
+            .batches
+            .get(i + 1)
+            .map_or(self.indices.len(), |next| next.start);
+        let mut split = Vec::with_capacity(3);
+        if batch.start < range.start {
+            split.push(batch);
+        }
+        split.push(Batch {
+            start: range.start,
+            texture,
+        });
+        if range.end < end {
+            split.push(Batch {
+                start: range.end,
+                texture: batch.texture,
+            });
+        }
+        self.batches.splice(i..i + 1, split);
+        self.batches
+            .dedup_by(|next, batch| next.texture == batch.texture);
+    }
+
+    /// Stores the mesh data in a free slot and returns a handle to it.
+    fn insert(&mut self, data: MeshData) -> Mesh {
+        match self.free_slots.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.generation = slot.generation.wrapping_add(1);
+                slot.data = Some(data);
+                Mesh {
+                    canvas: self.id,
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    data: Some(data),
+                });
+                Mesh {
+                    canvas: self.id,
+                    index: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        }
+    }
+
+    fn update_transform(
+        &mut self,
+        mesh: &Mesh,
+        update: impl FnOnce(&mut Transform),
+    ) -> Result<(), Error> {
+        let transform_index = self.data(mesh)?.transform_index;
+        update(&mut self.transforms[transform_index]);
+        mark_range(
+            &mut self.dirty.transforms,
+            transform_index..transform_index + 1,
+        );
+        Ok(())
+    }
+
+    fn data(&self, mesh: &Mesh) -> Result<&MeshData, Error> {
+        self.slots
+            .get(mesh.index as usize)
+            .filter(|slot| mesh.canvas == self.id && slot.generation == mesh.generation)
+            .and_then(|slot| slot.data.as_ref())
+            .ok_or(Error::MeshNotFound(*mesh))
+    }
+
+    /// Returns the texture added to this canvas, built in textures have none.
+    fn source(&self, texture: TextureHandle) -> Result<&TextureSource, Error> {
+        texture
+            .index()
+            .checked_sub(TextureHandle::FIRST as usize)
+            .filter(|_| texture.canvas() == self.id)
+            .and_then(|i| self.textures.get(i))
+            .ok_or(Error::TextureNotFound(texture))
+    }
+}
 
-        self.vertices
-            .extend(quad_vertices(layout.transform_index as u32, w, h));
-        self.indices.extend(quad_indices(layout.vertex_range.start));
-        self.transforms.push(transform(x, y, 0.0));
-        layout
+/// Returns the texture that meshes using the texture are drawn with,
+/// and the region of it that their texture coordinates are mapped to.
+fn resolve(textures: &[TextureSource], texture: TextureHandle) -> (TextureHandle, Rect) {
+    let source = texture
+        .index()
+        .checked_sub(TextureHandle::FIRST as usize)
+        .and_then(|i| textures.get(i));
+    match source {
+        Some(TextureSource::Packed { page, region, .. }) => (*page, *region),
+        _ => (texture, atlas::WHOLE),
     }
+}
 
-    pub fn delete(&mut self, layout: &Mesh) {
-        let num_vertices = self.vertices.len();
-        // Update the transform index of each vertex since we will delete one transform index
-        for vertex in &mut self.vertices[layout.vertex_range.end..num_vertices] {
-            vertex.transform_index -= 1;
+/// Adds the range to the parts of a canvas buffer that need to be uploaded.
+///
+/// Ranges that overlap or touch are merged, as are the two closest ones while there are
+/// more than `MAX_DIRTY_RANGES`, so edits far apart don't upload everything in between.
+fn mark_range(dirty: &mut Vec<Range<usize>>, range: Range<usize>) {
+    if range.start >= range.end {
+        return;
+    }
+    let mut merged = range;
+    dirty.retain(|other| {
+        let touching = other.start <= merged.end && merged.start <= other.end;
+        if touching {
+            merged = merged.start.min(other.start)..merged.end.max(other.end);
         }
-        self.transforms.remove(layout.transform_index);
-        self.vertices.drain(layout.vertex_range.clone());
+        !touching
+    });
+    let position = dirty
+        .iter()
+        .position(|other| other.start > merged.start)
+        .unwrap_or_else(|| dirty.len());
+    dirty.insert(position, merged);
+    while dirty.len() > MAX_DIRTY_RANGES {
+        let closest = (0..dirty.len() - 1)
+            .min_by_key(|&i| dirty[i + 1].start - dirty[i].end)
+            .expect("There are several ranges to merge");
+        let next = dirty.remove(closest + 1);
+        dirty[closest].end = next.end;
+    }
+}
+
+/// Adds the rectangle to the part of a texture that needs to be uploaded.
+fn mark_dirty(dirty: &mut Option<TexelRect>, rect: TexelRect) {
+    *dirty = Some(match *dirty {
+        Some(dirty) => dirty.union(rect),
+        None => rect,
+    });
+}
 
-        let num_indices = self.indices.len();
-        // Update the indices since we are deleting a slice of them and they would otherwise be out of sync
-        for index in self.indices[layout.index_range.end..num_indices].iter_mut() {
-            *index -= layout.vertex_range.len() as u32
+impl std::fmt::Debug for TextureSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Images are too large to be useful in debug output
+        match self {
+            TextureSource::Image {
+                image,
+                version,
+                options,
+                dirty,
+            } => f
+                .debug_struct("Image")
+                .field("dimensions", &image.dimensions())
+                .field("version", version)
+                .field("options", options)
+                .field("dirty", dirty)
+                .finish(),
+            TextureSource::Packed {
+                page,
+                region,
+                texels,
+            } => f
+                .debug_struct("Packed")
+                .field("page", page)
+                .field("region", region)
+                .field("texels", texels)
+                .finish(),
         }
-        self.indices.drain(layout.index_range.clone());
     }
 }
 
@@ -88,35 +1229,171 @@ fn transform(x: f32, y: f32, z: f32) -> Transform {
         ..Default::default()
     }
 }
-fn vertices(transform_index: u32, positions: &[Vec3]) -> Vec<Vertex> {
+fn vertices(positions: &[Vec3]) -> Vec<Vertex> {
     positions
         .iter()
         .map(|position| Vertex {
             position: *position,
-            transform_index,
             ..Default::default()
         })
         .collect()
 }
+fn check_attribute_count(
+    attribute: &'static str,
+    count: usize,
+    expected: usize,
+) -> Result<(), Error> {
+    if count == expected {
+        Ok(())
+    } else {
+        Err(Error::AttributeCount {
+            attribute,
+            count,
+            expected,
+        })
+    }
+}
+
+/// Describes custom geometry that is added to a [`Canvas`] with [`Canvas::mesh`].
+///
+/// Positions are in the local space of the mesh, every 3 indices form a triangle.
+/// Triangles must be wound counter-clockwise to face the camera, otherwise they are culled.
+///
+/// [`Canvas`]: struct.Canvas.html
+/// [`Canvas::mesh`]: struct.Canvas.html#method.mesh
+#[derive(Clone, Debug)]
+pub struct MeshBuilder {
+    positions: Vec<Vec3>,
+    indices: Vec<u32>,
+    color: Rgba,
+    colors: Option<Vec<Rgba>>,
+    tex_coords: Option<Vec<Vec2>>,
+    mix_factor: f32,
+    transform: Transform,
+    texture: TextureHandle,
+}
+
+impl Default for MeshBuilder {
+    fn default() -> Self {
+        Self {
+            positions: Vec::new(),
+            indices: Vec::new(),
+            color: WHITE,
+            colors: None,
+            tex_coords: None,
+            mix_factor: 0.0,
+            transform: Transform::default(),
+            texture: TextureHandle::default(),
+        }
+    }
+}
+
+impl MeshBuilder {
+    pub fn new(positions: Vec<Vec3>, indices: Vec<u32>) -> Self {
+        Self {
+            positions,
+            indices,
+            ..Default::default()
+        }
+    }
+
+    pub fn positions(&self) -> &[Vec3] {
+        &self.positions
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    /// Sets the color of every vertex, white by default.
+    pub fn with_color(mut self, color: Rgba) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Sets the color of each vertex, there must be one color per position.
+    pub fn with_colors(mut self, colors: Vec<Rgba>) -> Self {
+        self.colors = Some(colors);
+        self
+    }
+
+    /// Sets the texture coordinates of each vertex, there must be one per position.
+    pub fn with_tex_coords(mut self, tex_coords: Vec<Vec2>) -> Self {
+        self.tex_coords = Some(tex_coords);
+        self
+    }
+
+    /// Sets the mix factor between color and texture of every vertex.
+    ///
+    /// At 1.0 the texture is multiplied by the vertex color, so the color tints the texture
+    /// the same way it colors text.
+    pub fn with_mix_factor(mut self, mix_factor: f32) -> Self {
+        self.mix_factor = mix_factor;
+        self
+    }
+
+    /// Sets the texture that is sampled with the texture coordinates,
+    /// the mix factor controls how much it shows.
+    pub fn with_texture(mut self, texture: TextureHandle) -> Self {
+        self.texture = texture;
+        self
+    }
+
+    /// Sets the position of the mesh's local origin.
+    pub fn with_translation(mut self, translation: Vec3) -> Self {
+        self.transform.translation = translation;
+        self
+    }
+
+    /// Sets the rotation of the mesh around its pivot.
+    pub fn with_rotation(mut self, rotation: Quat) -> Self {
+        self.transform.rotation = rotation;
+        self
+    }
+
+    /// Sets the scale of the mesh along each axis around its pivot.
+    pub fn with_scale(mut self, scale: Vec3) -> Self {
+        self.transform.scale = scale;
+        self
+    }
+
+    /// Sets the point in local space that the mesh is rotated and scaled around.
+    pub fn with_pivot(mut self, pivot: Vec3) -> Self {
+        self.transform.pivot = pivot;
+        self
+    }
+}
 
 #[repr(C)]
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Copy, Clone, Debug)]
 pub struct Vertex {
     /// Position of the vertex in local space.
     pub position: Vec3,
-    /// Color of the vertex.
+    /// Color of the vertex, white by default so textures show unchanged.
     pub color: Vec4,
     /// Texture coordinates of the vertex to map a texture onto a mesh.
     pub tex_coords: Vec2,
     /// Mix factor between color and texture.
     /// 0.0 == color only
-    /// 1.0 == texture only
+    /// 1.0 == texture multiplied by color, a white color shows the texture alone
     pub mix_factor: f32,
     /// Index of the transform this vertex relates to.
     /// This is used to lookup "per mesh" data stored in the transform storage buffer e.g. scale, translation and rotation.
     pub transform_index: u32,
 }
 
+impl Default for Vertex {
+    fn default() -> Self {
+        Self {
+            position: [0.0, 0.0, 0.0],
+            color: WHITE,
+            tex_coords: [0.0, 0.0],
+            mix_factor: 0.0,
+            transform_index: 0,
+        }
+    }
+}
+
 impl Vertex {
     pub fn desc<'a>() -> wgpu::VertexBufferDescriptor<'a> {
         wgpu::VertexBufferDescriptor {
@@ -130,19 +1407,77 @@ impl Vertex {
 unsafe impl bytemuck::Pod for Vertex {}
 unsafe impl bytemuck::Zeroable for Vertex {}
 
+/// Position, rotation and scale of a mesh.
+///
+/// Uploaded to the transform storage buffer as it is, the vertex shader builds the model matrix.
 #[repr(C)]
-#[derive(Copy, Clone, Default, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Transform {
     pub translation: Vec3,
     pub rotation: Quat,
+    pub scale: Vec3,
+    /// Point in the mesh's local space that it is rotated and scaled around.
+    pub pivot: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: [0.0, 0.0, 0.0],
+            rotation: [1.0, 0.0, 0.0, 0.0],
+            scale: [1.0, 1.0, 1.0],
+            pivot: [0.0, 0.0, 0.0],
+        }
+    }
 }
 
 impl Transform {
-    /// Converts the position and rotation into a 4x4 transform matrix.
+    /// Converts the position, rotation, scale and pivot into a 4x4 transform matrix.
     pub fn to_matrix(&self) -> Mat4 {
-        (self.translation.to_translation_matrix() * self.rotation.to_rotation_matrix()).into()
+        let [x, y, z] = self.pivot;
+        (self.translation.to_translation_matrix()
+            * self.pivot.to_translation_matrix()
+            * self.rotation.to_rotation_matrix()
+            * self.scale.to_scale_matrix()
+            * [-x, -y, -z].to_translation_matrix())
+        .into()
     }
 }
 
 unsafe impl bytemuck::Pod for Transform {}
 unsafe impl bytemuck::Zeroable for Transform {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges_touching_ranges() {
+        let mut dirty = Vec::new();
+        mark_range(&mut dirty, 10..20);
+        mark_range(&mut dirty, 0..5);
+        mark_range(&mut dirty, 30..40);
+        mark_range(&mut dirty, 7..7);
+        assert_eq!(dirty, vec![0..5, 10..20, 30..40]);
+        mark_range(&mut dirty, 20..30);
+        assert_eq!(dirty, vec![0..5, 10..40]);
+        mark_range(&mut dirty, 3..12);
+        assert_eq!(dirty, vec![0..40]);
+    }
+
+    #[test]
+    fn limits_the_number_of_ranges() {
+        let mut dirty = Vec::new();
+        for i in 0..MAX_DIRTY_RANGES {
+            mark_range(&mut dirty, i * 100..i * 100 + 1);
+        }
+        assert_eq!(dirty.len(), MAX_DIRTY_RANGES);
+        // The closest neighbours are merged rather than spanning the whole buffer
+        mark_range(&mut dirty, 1000..1001);
+        mark_range(&mut dirty, 1003..1004);
+        assert_eq!(dirty.len(), MAX_DIRTY_RANGES);
+        assert_eq!(dirty[0], 0..101);
+        assert_eq!(dirty[1], 200..201);
+        assert_eq!(dirty[MAX_DIRTY_RANGES - 1], 1000..1004);
+    }
+}
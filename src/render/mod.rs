@@ -1,9 +1,18 @@
 /// Batteries
 pub mod display;
+pub mod framebuffer;
+pub mod headless;
 pub mod math;
 pub mod mesh;
+pub mod path;
+pub mod shape;
+pub mod sprite;
+pub mod stroke;
+pub mod svg;
+pub mod text;
 
 /// Graphics.
+mod atlas;
 pub mod camera;
 pub mod renderer;
 pub mod texture;
@@ -11,3 +20,6 @@ pub mod uniform;
 
 /// Errors.
 pub mod error;
+
+/// Testing.
+pub mod golden;
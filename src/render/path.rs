@@ -0,0 +1,505 @@
+//! Vector paths made of lines, Bézier curves and arcs that can be filled or stroked.
+use super::math::*;
+use super::mesh::MeshBuilder;
+use super::shape::{self, Triangles};
+use super::stroke::{self, StrokeStyle};
+use std::f32::consts::PI;
+
+/// Rule that decides which regions enclosed by a path are inside it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FillRule {
+    /// Inside where the outlines wind around the point a non-zero number of times.
+    NonZero,
+    /// Inside where a ray from the point crosses the outlines an odd number of times.
+    EvenOdd,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Segment {
+    MoveTo(Vec2),
+    LineTo(Vec2),
+    QuadTo(Vec2, Vec2),
+    CubicTo(Vec2, Vec2, Vec2),
+    ArcTo {
+        radii: Vec2,
+        x_rotation: f32,
+        large_arc: bool,
+        sweep: bool,
+        to: Vec2,
+    },
+    Close,
+}
+
+/// A vector path made of one or more subpaths.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Path {
+    segments: Vec<Segment>,
+}
+
+/// A flattened subpath, curves are replaced by straight segments.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Polyline {
+    pub points: Vec<Vec2>,
+    pub closed: bool,
+}
+
+impl Path {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts a new subpath at the point.
+    pub fn move_to(mut self, to: Vec2) -> Self {
+        self.segments.push(Segment::MoveTo(to));
+        self
+    }
+
+    pub fn line_to(mut self, to: Vec2) -> Self {
+        self.segments.push(Segment::LineTo(to));
+        self
+    }
+
+    /// Adds a quadratic Bézier curve with one control point.
+    pub fn quad_to(mut self, ctrl: Vec2, to: Vec2) -> Self {
+        self.segments.push(Segment::QuadTo(ctrl, to));
+        self
+    }
+
+    /// Adds a cubic Bézier curve with two control points.
+    pub fn cubic_to(mut self, ctrl1: Vec2, ctrl2: Vec2, to: Vec2) -> Self {
+        self.segments.push(Segment::CubicTo(ctrl1, ctrl2, to));
+        self
+    }
+
+    /// Adds an elliptical arc with the same parameters as the SVG arc command.
+    ///
+    /// The ellipse has the radii and is rotated by `x_rotation` radians,
+    /// of the possible arcs to the point `large_arc` picks the one that sweeps more than 180 degrees
+    /// and `sweep` picks the one drawn in the direction of increasing angles.
+    /// Radii that are too small to reach the point are scaled up.
+    pub fn arc_to(
+        mut self,
+        radii: Vec2,
+        x_rotation: f32,
+        large_arc: bool,
+        sweep: bool,
+        to: Vec2,
+    ) -> Self {
+        self.segments.push(Segment::ArcTo {
+            radii,
+            x_rotation,
+            large_arc,
+            sweep,
+            to,
+        });
+        self
+    }
+
+    /// Closes the current subpath with a line back to its start.
+    pub fn close(mut self) -> Self {
+        self.segments.push(Segment::Close);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Replaces the curves with straight segments that are within the tolerance of the curve.
+    pub fn flatten(&self, tolerance: f32) -> Vec<Polyline> {
+        let mut polylines = Vec::new();
+        let mut current = Polyline {
+            points: Vec::new(),
+            closed: false,
+        };
+        let mut last = [0.0, 0.0];
+        let mut start = [0.0, 0.0];
+        for segment in &self.segments {
+            if current.points.is_empty() {
+                current.points.push(last);
+            }
+            match *segment {
+                Segment::MoveTo(to) => {
+                    finish(&mut polylines, &mut current);
+                    current.points.push(to);
+                    start = to;
+                    last = to;
+                }
+                Segment::LineTo(to) => {
+                    current.points.push(to);
+                    last = to;
+                }
+                Segment::QuadTo(ctrl, to) => {
+                    let n = wang(&[last, ctrl, to], 2, tolerance);
+                    current.points.extend((1..=n).map(|i| {
+                        let t = i as f32 / n as f32;
+                        let mt = 1.0 - t;
+                        lerp3(last, ctrl, to, mt * mt, 2.0 * mt * t, t * t)
+                    }));
+                    last = to;
+                }
+                Segment::CubicTo(ctrl1, ctrl2, to) => {
+                    let n = wang(&[last, ctrl1, ctrl2, to], 3, tolerance);
+                    current.points.extend((1..=n).map(|i| {
+                        let t = i as f32 / n as f32;
+                        let mt = 1.0 - t;
+                        let [x, y] = lerp3(
+                            last,
+                            ctrl1,
+                            ctrl2,
+                            mt * mt * mt,
+                            3.0 * mt * mt * t,
+                            3.0 * mt * t * t,
+                        );
+                        [x + t * t * t * to[0], y + t * t * t * to[1]]
+                    }));
+                    last = to;
+                }
+                Segment::ArcTo {
+                    radii,
+                    x_rotation,
+                    large_arc,
+                    sweep,
+                    to,
+                } => {
+                    arc(
+                        &mut current.points,
+                        last,
+                        radii,
+                        x_rotation,
+                        large_arc,
+                        sweep,
+                        to,
+                        tolerance,
+                    );
+                    last = to;
+                }
+                Segment::Close => {
+                    current.closed = true;
+                    finish(&mut polylines, &mut current);
+                    last = start;
+                }
+            }
+        }
+        finish(&mut polylines, &mut current);
+        polylines
+    }
+}
+
+/// Ends the polyline being built, polylines without a segment are dropped.
+fn finish(polylines: &mut Vec<Polyline>, current: &mut Polyline) {
+    let polyline = std::mem::replace(
+        current,
+        Polyline {
+            points: Vec::new(),
+            closed: false,
+        },
+    );
+    if polyline.points.len() > 1 {
+        polylines.push(polyline);
+    }
+}
+
+/// Number of segments that keep a Bézier curve of the degree within tolerance (Wang's formula).
+fn wang(points: &[Vec2], degree: usize, tolerance: f32) -> usize {
+    let max_second_difference = points
+        .windows(3)
+        .map(|w| {
+            let x = w[0][0] - 2.0 * w[1][0] + w[2][0];
+            let y = w[0][1] - 2.0 * w[1][1] + w[2][1];
+            (x * x + y * y).sqrt()
+        })
+        .fold(0.0, f32::max);
+    let factor = (degree * (degree - 1)) as f32 / 8.0;
+    let n = (factor * max_second_difference / tolerance).sqrt().ceil();
+    (n as usize).clamp(1, shape::MAX_SEGMENTS)
+}
+
+fn lerp3(a: Vec2, b: Vec2, c: Vec2, wa: f32, wb: f32, wc: f32) -> Vec2 {
+    [
+        a[0] * wa + b[0] * wb + c[0] * wc,
+        a[1] * wa + b[1] * wb + c[1] * wc,
+    ]
+}
+
+/// Flattens an SVG style endpoint arc, see the SVG implementation notes on arc conversion.
+#[allow(clippy::too_many_arguments)]
+fn arc(
+    points: &mut Vec<Vec2>,
+    from: Vec2,
+    radii: Vec2,
+    x_rotation: f32,
+    large_arc: bool,
+    sweep: bool,
+    to: Vec2,
+    tolerance: f32,
+) {
+    let (mut rx, mut ry) = (radii[0].abs(), radii[1].abs());
+    if from == to {
+        return;
+    }
+    if rx <= f32::EPSILON || ry <= f32::EPSILON {
+        points.push(to);
+        return;
+    }
+    let (sin, cos) = x_rotation.sin_cos();
+    let dx2 = (from[0] - to[0]) * 0.5;
+    let dy2 = (from[1] - to[1]) * 0.5;
+    let x1 = cos * dx2 + sin * dy2;
+    let y1 = -sin * dx2 + cos * dy2;
+
+    let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+    let numerator = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+    let denominator = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+    let sign = if large_arc == sweep { -1.0 } else { 1.0 };
+    let coefficient = sign * (numerator / denominator).max(0.0).sqrt();
+    let cx1 = coefficient * rx * y1 / ry;
+    let cy1 = -coefficient * ry * x1 / rx;
+    let cx = cos * cx1 - sin * cy1 + (from[0] + to[0]) * 0.5;
+    let cy = sin * cx1 + cos * cy1 + (from[1] + to[1]) * 0.5;
+
+    let u = [(x1 - cx1) / rx, (y1 - cy1) / ry];
+    let v = [(-x1 - cx1) / rx, (-y1 - cy1) / ry];
+    let start = u[1].atan2(u[0]);
+    let mut delta = (u[0] * v[1] - u[1] * v[0]).atan2(u[0] * v[0] + u[1] * v[1]);
+    if !sweep && delta > 0.0 {
+        delta -= 2.0 * PI;
+    } else if sweep && delta < 0.0 {
+        delta += 2.0 * PI;
+    }
+
+    let n = shape::segments(rx.max(ry), delta, tolerance);
+    points.extend((1..n).map(|i| {
+        let angle = start + delta * i as f32 / n as f32;
+        let (x, y) = (rx * angle.cos(), ry * angle.sin());
+        [cx + cos * x - sin * y, cy + sin * x + cos * y]
+    }));
+    // Land exactly on the end point so following segments connect
+    points.push(to);
+}
+
+/// Fills the area enclosed by the polylines according to the fill rule.
+///
+/// Open polylines are closed implicitly. The outlines may overlap and intersect themselves,
+/// the area is split into horizontal slabs at every vertex and intersection and each slab
+/// is filled with trapezoids between the edges that cross it.
+pub fn fill(polylines: &[Polyline], rule: FillRule) -> MeshBuilder {
+    let mut triangles = Triangles::default();
+    let edges = polylines
+        .iter()
+        .flat_map(|polyline| {
+            let n = polyline.points.len();
+            (0..n).filter_map(move |i| Edge::new(polyline.points[i], polyline.points[(i + 1) % n]))
+        })
+        .collect::<Vec<_>>();
+
+    let mut ys = edges
+        .iter()
+        .flat_map(|edge| vec![edge.top[1], edge.bottom[1]])
+        .collect::<Vec<_>>();
+    for (i, a) in edges.iter().enumerate() {
+        for b in &edges[i + 1..] {
+            if let Some(y) = a.intersection(b) {
+                ys.push(y);
+            }
+        }
+    }
+    ys.sort_by(f32::total_cmp);
+    ys.dedup_by(|a, b| (*a - *b).abs() <= f32::EPSILON);
+
+    let mut crossings = Vec::new();
+    for slab in ys.windows(2) {
+        let (y0, y1) = (slab[0], slab[1]);
+        let mid = (y0 + y1) * 0.5;
+        crossings.clear();
+        crossings.extend(
+            edges
+                .iter()
+                .filter(|edge| {
+                    edge.top[1] <= y0 + f32::EPSILON && edge.bottom[1] >= y1 - f32::EPSILON
+                })
+                .map(|edge| (edge.x_at(mid), edge)),
+        );
+        crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut winding = 0;
+        for pair in crossings.windows(2) {
+            let (left, right) = (pair[0].1, pair[1].1);
+            winding += left.winding;
+            let inside = match rule {
+                FillRule::NonZero => winding != 0,
+                FillRule::EvenOdd => winding % 2 != 0,
+            };
+            if inside {
+                let a = triangles.vertex([left.x_at(y0), y0]);
+                let b = triangles.vertex([right.x_at(y0), y0]);
+                let c = triangles.vertex([right.x_at(y1), y1]);
+                let d = triangles.vertex([left.x_at(y1), y1]);
+                triangles.quad(a, b, c, d);
+            }
+        }
+    }
+    triangles.build()
+}
+
+/// Strokes every polyline with the style as a single mesh.
+pub fn stroke(polylines: &[Polyline], style: &StrokeStyle, tolerance: f32) -> MeshBuilder {
+    let mut triangles = Triangles::default();
+    for polyline in polylines {
+        stroke::stroke(
+            &mut triangles,
+            &polyline.points,
+            polyline.closed,
+            style,
+            tolerance,
+        );
+    }
+    triangles.build()
+}
+
+/// A non-horizontal edge of a filled outline, ordered from its lowest to its highest y.
+struct Edge {
+    top: Vec2,
+    bottom: Vec2,
+    /// +1 if the outline goes up along the edge, -1 if it goes down.
+    winding: i32,
+}
+
+impl Edge {
+    /// Horizontal edges and edges with a coordinate that is not finite are skipped,
+    /// neither can be crossed by a slab.
+    fn new(from: Vec2, to: Vec2) -> Option<Self> {
+        if !from.iter().chain(&to).all(|c| c.is_finite()) || (from[1] - to[1]).abs() <= f32::EPSILON
+        {
+            None
+        } else if from[1] < to[1] {
+            Some(Self {
+                top: from,
+                bottom: to,
+                winding: 1,
+            })
+        } else {
+            Some(Self {
+                top: to,
+                bottom: from,
+                winding: -1,
+            })
+        }
+    }
+
+    fn x_at(&self, y: f32) -> f32 {
+        let t = (y - self.top[1]) / (self.bottom[1] - self.top[1]);
+        self.top[0] + t * (self.bottom[0] - self.top[0])
+    }
+
+    /// The y where the edges cross strictly between their end points, if they do.
+    fn intersection(&self, other: &Edge) -> Option<f32> {
+        let y0 = self.top[1].max(other.top[1]);
+        let y1 = self.bottom[1].min(other.bottom[1]);
+        if y1 <= y0 {
+            return None;
+        }
+        let d0 = self.x_at(y0) - other.x_at(y0);
+        let d1 = self.x_at(y1) - other.x_at(y1);
+        if d0 * d1 >= 0.0 {
+            return None;
+        }
+        Some(y0 + (y1 - y0) * d0 / (d0 - d1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(min: f32, max: f32) -> Path {
+        Path::new()
+            .move_to([min, min])
+            .line_to([max, min])
+            .line_to([max, max])
+            .line_to([min, max])
+            .close()
+    }
+
+    fn area(builder: &MeshBuilder) -> f32 {
+        let point = |i: u32| builder.positions()[i as usize];
+        builder
+            .indices()
+            .chunks(3)
+            .map(|t| {
+                let ([ax, ay, _], [bx, by, _], [cx, cy, _]) =
+                    (point(t[0]), point(t[1]), point(t[2]));
+                ((bx - ax) * (cy - ay) - (by - ay) * (cx - ax)) * 0.5
+            })
+            .sum()
+    }
+
+    #[test]
+    fn flattens_subpaths() {
+        let path = square(0.0, 1.0).move_to([2.0, 0.0]).line_to([3.0, 0.0]);
+        let polylines = path.flatten(0.01);
+        assert_eq!(polylines.len(), 2);
+        assert_eq!(
+            polylines[0].points,
+            vec![[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]]
+        );
+        assert!(polylines[0].closed);
+        assert_eq!(polylines[1].points, vec![[2.0, 0.0], [3.0, 0.0]]);
+        assert!(!polylines[1].closed);
+    }
+
+    #[test]
+    fn flattens_curves_within_tolerance() {
+        let tolerance = 0.001;
+        let path = Path::new()
+            .move_to([0.0, 0.0])
+            .quad_to([1.0, 2.0], [2.0, 0.0]);
+        let points = &path.flatten(tolerance)[0].points;
+        assert!(points.len() > 2);
+        assert_eq!(*points.last().unwrap(), [2.0, 0.0]);
+        // Every point lies on the parabola y = 2x - x², the chords stay within tolerance of it
+        for pair in points.windows(2) {
+            let [x, y] = pair[0];
+            assert!((y - (2.0 * x - x * x)).abs() < 1e-4);
+            let mid_x = (pair[0][0] + pair[1][0]) * 0.5;
+            let mid_y = (pair[0][1] + pair[1][1]) * 0.5;
+            assert!((2.0 * mid_x - mid_x * mid_x) - mid_y <= tolerance);
+        }
+        // A coarser tolerance needs fewer segments
+        assert!(path.flatten(0.1)[0].points.len() < points.len());
+    }
+
+    #[test]
+    fn fills_according_to_the_rule() {
+        let outer = square(0.0, 4.0).flatten(0.01).remove(0);
+        let inner = square(1.0, 3.0).flatten(0.01).remove(0);
+        let mut reversed = inner.clone();
+        reversed.points.reverse();
+
+        let square = fill(&[outer.clone()], FillRule::NonZero);
+        assert!((area(&square) - 16.0).abs() < 1e-4);
+        // Every triangle faces the camera, so none has a negative area
+        assert!((area(&square) - area(&fill(&[outer.clone()], FillRule::EvenOdd))).abs() < 1e-4);
+
+        let both = [outer.clone(), inner];
+        assert!((area(&fill(&both, FillRule::NonZero)) - 16.0).abs() < 1e-4);
+        assert!((area(&fill(&both, FillRule::EvenOdd)) - 12.0).abs() < 1e-4);
+        let hole = [outer, reversed];
+        assert!((area(&fill(&hole, FillRule::NonZero)) - 12.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn fill_skips_points_that_are_not_finite() {
+        let path = Path::new()
+            .move_to([0.0, 0.0])
+            .line_to([1.0, 0.0])
+            .line_to([f32::NAN, 1.0])
+            .line_to([0.0, f32::INFINITY])
+            .close();
+        fill(&path.flatten(0.01), FillRule::NonZero);
+    }
+}
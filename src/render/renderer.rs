@@ -1,10 +1,23 @@
-use super::camera::Projection;
+use super::error::Error;
 use super::math::*;
+use super::mesh::Canvas;
 use super::mesh::Transform;
 use super::mesh::Vertex;
-use super::texture::Texture;
+use super::texture::{TexelRect, Texture, TextureHandle, TextureOptions};
 use super::uniform::Uniform;
 use crate::render::camera::Camera;
+use std::ops::Range;
+
+/// Two counter clockwise triangles covering a quad with corners listed clockwise from the top left.
+const QUAD_INDICES: [u32; 6] = [0, 2, 1, 0, 3, 2];
+
+/// Number of elements a canvas buffer has room for when it is created.
+const MIN_BUFFER_CAPACITY: usize = 256;
+
+/// Texture format of the swap chain used when presenting to a surface.
+const SURFACE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Bgra8UnormSrgb;
+/// Texture format of the offscreen target, matches the layout of an `image::RgbaImage`.
+const OFFSCREEN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
 
 pub(crate) struct Renderer {
     device: wgpu::Device,
@@ -13,6 +26,8 @@ pub(crate) struct Renderer {
     bindings: Bindings,
     buffers: Buffers,
     pipeline: Pipeline,
+    framebuffer: FramebufferQuad,
+    target: Target,
 }
 struct Layouts {
     uniform_bind_group_layout: wgpu::BindGroupLayout,
@@ -21,32 +36,130 @@ struct Layouts {
     pipeline_layout: wgpu::PipelineLayout,
 }
 struct Bindings {
-    diffuse_bind_group: wgpu::BindGroup,
+    /// Canvas whose data the buffers and textures hold, `None` until the first frame.
+    canvas: Option<u64>,
+    /// Uploaded canvas textures indexed by their handle, the first is always a white pixel.
+    textures: Vec<Option<BoundTexture>>,
+    /// Camera and target size that the uniform buffer was last written with,
+    /// `None` until the first frame.
+    view: Option<(Camera, (u32, u32))>,
+    /// Area of the target that the camera draws to.
+    viewport: Rect,
+    uniform_buffer: wgpu::Buffer,
     uniform_bind_group: wgpu::BindGroup,
     transform_bind_group: wgpu::BindGroup,
 }
-struct Buffers {
+/// A texture with the bind group used to sample it as the diffuse texture.
+///
+/// Double buffered textures keep two copies that are drawn in turns, updates are written
+/// to the copy that wasn't drawn last frame.
+struct BoundTexture {
+    buffers: Vec<(Texture, wgpu::BindGroup)>,
+    /// Index of the copy that is drawn.
+    front: usize,
+    /// Part of the other copy that is older than the one drawn.
+    stale: Option<TexelRect>,
+    /// Version of the canvas texture that was created.
+    version: u64,
+}
+/// Draws the canvas framebuffer as a quad filling its viewport, unaffected by the camera.
+struct FramebufferQuad {
+    texture: Option<BoundTexture>,
     vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
+    /// Identity view projection and model matrix, so the quad covers the viewport.
+    uniform_bind_group: wgpu::BindGroup,
+    transform_bind_group: wgpu::BindGroup,
+}
+/// Canvas buffers, kept between frames and only written where the canvas changed.
+struct Buffers {
+    vertex_buffer: GrowableBuffer,
+    index_buffer: GrowableBuffer,
+    transform_buffer: GrowableBuffer,
+}
+/// A GPU buffer holding an array of elements that is recreated larger when the array outgrows it.
+struct GrowableBuffer {
+    buffer: wgpu::Buffer,
+    usage: wgpu::BufferUsage,
+    label: &'static str,
+    /// Size of an element in bytes.
+    stride: usize,
+    /// Number of elements the buffer has room for.
+    capacity: usize,
+    /// Number of elements written so far.
+    len: usize,
 }
 struct Pipeline {
-    swap_chain_descriptor: wgpu::SwapChainDescriptor,
-    swap_chain: wgpu::SwapChain,
     render_pipeline: wgpu::RenderPipeline,
 }
+/// How drawing continues after acquiring a frame from the swap chain failed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Recovery {
+    /// Skips the frame, the next one will likely succeed.
+    Skip,
+    /// Creates the swap chain again, then tries to acquire the frame once more.
+    RecreateSwapChain,
+    /// Creates the swap chain again to release its textures and skips the frame.
+    ReleaseSwapChain,
+}
+/// Where the renderer draws its frames to.
+enum Target {
+    /// A swap chain that presents to a window surface.
+    Surface(Onscreen),
+    /// A texture that is never presented, its contents are read back to the CPU instead.
+    Offscreen(Offscreen),
+}
+struct Onscreen {
+    surface: wgpu::Surface,
+    swap_chain_descriptor: wgpu::SwapChainDescriptor,
+    /// `None` while the surface has no area to draw to, e.g. when the window is minimized.
+    swap_chain: Option<wgpu::SwapChain>,
+}
+struct Offscreen {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    width: u32,
+    height: u32,
+}
 
 impl Renderer {
     pub fn new(
-        surface: &wgpu::Surface,
+        surface: wgpu::Surface,
         device: wgpu::Device,
         queue: wgpu::Queue,
         width: u32,
         height: u32,
     ) -> Self {
+        let swap_chain_descriptor = wgpu::SwapChainDescriptor {
+            usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+            format: SURFACE_FORMAT,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::Mailbox,
+        };
+        let mut onscreen = Onscreen {
+            surface,
+            swap_chain_descriptor,
+            swap_chain: None,
+        };
+        onscreen.recreate(&device);
+        Self::with_target(device, queue, Target::Surface(onscreen))
+    }
+
+    /// Creates a renderer that draws into an offscreen texture instead of a window surface.
+    ///
+    /// Frames are retrieved with [`render_to_image`](#method.render_to_image).
+    pub fn new_headless(device: wgpu::Device, queue: wgpu::Queue, width: u32, height: u32) -> Self {
+        let target = Target::Offscreen(Offscreen::new(&device, width, height));
+        Self::with_target(device, queue, target)
+    }
+
+    fn with_target(device: wgpu::Device, queue: wgpu::Queue, target: Target) -> Self {
         let layouts = Layouts::new(&device);
-        let bindings = Bindings::new(&device, &queue, &layouts);
         let buffers = Buffers::new(&device);
-        let pipeline = Pipeline::new(&device, surface, &layouts, width, height);
+        let bindings = Bindings::new(&device, &queue, &layouts, &buffers);
+        let pipeline = Pipeline::new(&device, &layouts, target.format());
+        let framebuffer = FramebufferQuad::new(&device, &layouts);
         Self {
             device,
             queue,
@@ -54,99 +167,388 @@ impl Renderer {
             bindings,
             buffers,
             pipeline,
+            framebuffer,
+            target,
         }
     }
 
-    /// Draws a single frame to the swap chain then immediately presents it to the provided surface.
+    /// Returns the first adapter that is compatible with the surface, if any.
+    pub async fn request_adapter(
+        instance: &wgpu::Instance,
+        compatible_surface: Option<&wgpu::Surface>,
+    ) -> Result<wgpu::Adapter, Error> {
+        instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::Default,
+                compatible_surface,
+            })
+            .await
+            .ok_or(Error::AdapterNotFound)
+    }
+
+    pub async fn request_device(
+        adapter: &wgpu::Adapter,
+    ) -> Result<(wgpu::Device, wgpu::Queue), Error> {
+        let trace_dir = std::env::var("WGPU_TRACE");
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    // Samplers fall back to no anisotropy on devices without it
+                    features: adapter.features() & wgpu::Features::SAMPLER_ANISOTROPY,
+                    shader_validation: true,
+                    ..Default::default()
+                },
+                trace_dir.ok().as_ref().map(std::path::Path::new),
+            )
+            .await?;
+        Ok((device, queue))
+    }
+
+    /// Draws a single frame to the swap chain then immediately presents it to the surface.
+    ///
+    /// Nothing is drawn while the surface has no area, or when no frame can be acquired
+    /// even after recreating the swap chain, the next call tries again.
     ///
     /// TODO:
     /// Provide some control over the "render rate" vs "frame rate"
     /// e.g. I could introduce 2 new methods
     /// - render_frame() => only renders the frame to prepare it for presentation, doesn't present
     /// - present_frame() => presents the next prepared swap chain frame
-    pub fn draw_frame(
-        &mut self,
-        surface: &wgpu::Surface,
-        vertices: &[Vertex],
-        indices: &[u32],
-        transforms: &[Transform],
-    ) {
-        let transform_data = transforms
-            .iter()
-            .map(Transform::to_matrix)
-            .collect::<Vec<Mat4>>();
-        let transform_buffer_size = transform_data.len() * std::mem::size_of::<Mat4>();
-        let transform_buffer = self.device.create_buffer_with_data(
-            bytemuck::cast_slice(&transform_data),
-            wgpu::BufferUsage::STORAGE,
-        );
-        let transform_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &self.layouts.transform_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: wgpu::BindingResource::Buffer(
-                    transform_buffer.slice(0..(transform_buffer_size as u64)),
-                ),
-            }],
-            label: Some("transform_bind_group"),
-        });
-
-        self.bindings.transform_bind_group = transform_bind_group;
+    pub fn draw_frame(&mut self, canvas: &mut Canvas, camera: &Camera) -> Result<(), Error> {
+        let frame = match self.next_frame()? {
+            Some(frame) => frame,
+            None => return Ok(()),
+        };
+        self.upload(canvas, camera);
+        let size = self.target_size();
 
-        self.buffers.vertex_buffer = self
+        let mut encoder = self
             .device
-            .create_buffer_with_data(bytemuck::cast_slice(vertices), wgpu::BufferUsage::VERTEX);
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Render Encoder"),
+            });
+        self.encode_pass(&mut encoder, &frame.output.view, canvas, size);
+        self.queue.submit(Some(encoder.finish()));
+        Ok(())
+    }
 
-        self.buffers.index_buffer = self
-            .device
-            .create_buffer_with_data(bytemuck::cast_slice(indices), wgpu::BufferUsage::INDEX);
-
-        let frame = match self.pipeline.swap_chain.get_next_frame() {
-            Ok(frame) => frame,
-            Err(_) => {
-                self.pipeline.swap_chain = self
-                    .device
-                    .create_swap_chain(&surface, &self.pipeline.swap_chain_descriptor);
-                self.pipeline
-                    .swap_chain
-                    .get_next_frame()
-                    .expect("Failed to acquire next swap chain texture!")
+    /// Acquires the next texture of the swap chain, or `None` if no frame can be drawn now.
+    ///
+    /// Only the swap chain is recovered. wgpu doesn't report a lost device, so a device that
+    /// is lost, e.g. when the driver resets, makes later wgpu calls fail and is not recovered.
+    fn next_frame(&mut self) -> Result<Option<wgpu::SwapChainFrame>, Error> {
+        let onscreen = match &mut self.target {
+            Target::Surface(onscreen) => onscreen,
+            Target::Offscreen(_) => {
+                log::warn!("Cannot draw a frame to a surface with a headless renderer");
+                return Ok(None);
+            }
+        };
+        // Acquiring fails when the surface changed since the swap chain was created,
+        // in which case it is recreated and acquiring is tried once more
+        for _ in 0..2 {
+            let swap_chain = match &mut onscreen.swap_chain {
+                Some(swap_chain) => swap_chain,
+                None => return Ok(None),
+            };
+            let error = match swap_chain.get_next_frame() {
+                Ok(frame) => return Ok(Some(frame)),
+                Err(error) => error,
+            };
+            match recovery(error) {
+                Recovery::Skip => {
+                    log::warn!("Timed out acquiring the next frame, skipping it");
+                    return Ok(None);
+                }
+                Recovery::RecreateSwapChain => {
+                    log::info!("Swap chain is outdated or lost, recreating it");
+                    onscreen.recreate(&self.device);
+                }
+                Recovery::ReleaseSwapChain => {
+                    log::error!("Out of memory acquiring the next frame, skipping it");
+                    onscreen.recreate(&self.device);
+                    return Ok(None);
+                }
             }
+        }
+        log::warn!("Failed to acquire the next frame after recreating the swap chain, skipping it");
+        Ok(None)
+    }
+
+    /// Draws a single frame into the offscreen target and reads the pixels back to the CPU.
+    ///
+    /// This blocks until the GPU has finished rendering the frame.
+    pub fn render_to_image(
+        &mut self,
+        canvas: &mut Canvas,
+        camera: &Camera,
+    ) -> Result<image::RgbaImage, Error> {
+        self.upload(canvas, camera);
+
+        let (width, height) = match &self.target {
+            Target::Offscreen(offscreen) => (offscreen.width, offscreen.height),
+            Target::Surface(_) => return Err(Error::NotHeadless),
         };
+        // Rows copied out of a texture must be aligned, the padding is stripped when reading back.
+        let unpadded_bytes_per_row = 4 * width;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = (unpadded_bytes_per_row + align - 1) / align * align;
+        let output_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("output_buffer"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
 
         let mut encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render Encoder"),
+                label: Some("Offscreen Encoder"),
             });
+        if let Target::Offscreen(offscreen) = &self.target {
+            self.encode_pass(&mut encoder, &offscreen.view, canvas, (width, height));
+            encoder.copy_texture_to_buffer(
+                wgpu::TextureCopyView {
+                    texture: &offscreen.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                wgpu::BufferCopyView {
+                    buffer: &output_buffer,
+                    layout: wgpu::TextureDataLayout {
+                        offset: 0,
+                        bytes_per_row: padded_bytes_per_row,
+                        rows_per_image: height,
+                    },
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth: 1,
+                },
+            );
+        }
+        self.queue.submit(Some(encoder.finish()));
+
+        let output_slice = output_buffer.slice(..);
+        let mapping = output_slice.map_async(wgpu::MapMode::Read);
+        self.device.poll(wgpu::Maintain::Wait);
+        futures::executor::block_on(mapping)?;
 
+        let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
         {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
-                    attachment: &frame.output.view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                        store: false,
-                    },
-                }],
-                depth_stencil_attachment: None,
-            });
+            let padded = output_slice.get_mapped_range();
+            for row in padded.chunks(padded_bytes_per_row as usize) {
+                pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+            }
+        }
+        output_buffer.unmap();
+
+        Ok(image::RgbaImage::from_raw(width, height, pixels)
+            .expect("Readback buffer does not match the offscreen target size!"))
+    }
+
+    /// Uploads the canvas data and camera to the GPU ready for the next render pass.
+    ///
+    /// Textures are created when they are new or their version changed, otherwise only
+    /// the parts of them that changed are written.
+    fn upload(&mut self, canvas: &mut Canvas, camera: &Camera) {
+        // The projection depends on the target size, so it is recomputed after a resize too
+        let (width, height) = self.target_size();
+        let view = Some((*camera, (width, height)));
+        if self.bindings.view != view {
+            let mut uniforms = Uniform::new();
+            uniforms.update_view_proj(camera, width, height);
+            self.queue.write_buffer(
+                &self.bindings.uniform_buffer,
+                0,
+                bytemuck::cast_slice(&[uniforms]),
+            );
+            self.bindings.view = view;
+            self.bindings.viewport = camera.viewport(width, height);
+        }
 
-            render_pass.set_pipeline(&self.pipeline.render_pipeline);
-            render_pass.set_bind_group(0, &self.bindings.diffuse_bind_group, &[]);
-            render_pass.set_bind_group(1, &self.bindings.uniform_bind_group, &[]);
-            render_pass.set_bind_group(2, &self.bindings.transform_bind_group, &[]);
-            render_pass.set_vertex_buffer(0, self.buffers.vertex_buffer.slice(..));
-            render_pass.set_index_buffer(self.buffers.index_buffer.slice(..));
-            render_pass.draw_indexed(0..indices.len() as u32, 0, 0..1);
+        // A different canvas than last frame has none of its data on the GPU yet,
+        // its buffers are written in full and its textures created
+        if self.bindings.canvas != Some(canvas.id()) {
+            self.bindings.canvas = Some(canvas.id());
+            self.bindings
+                .textures
+                .truncate(TextureHandle::WHITE.index() + 1);
+            self.buffers.vertex_buffer.invalidate();
+            self.buffers.index_buffer.invalidate();
+            self.buffers.transform_buffer.invalidate();
+        }
+
+        let textures = &mut self.bindings.textures;
+        for upload in canvas.textures() {
+            let index = upload.handle.index();
+            if textures.len() <= index {
+                textures.resize_with(index + 1, || None);
+            }
+            match &mut textures[index] {
+                Some(bound) if bound.version == upload.version => {
+                    if let Some(dirty) = upload.dirty {
+                        bound.write(&self.queue, upload.image, dirty);
+                    }
+                }
+                bound => {
+                    *bound = Some(BoundTexture::new(
+                        &self.device,
+                        &self.queue,
+                        &self.layouts,
+                        upload.image,
+                        &upload.options,
+                        upload.version,
+                        None,
+                    ));
+                }
+            }
+        }
+        match canvas.framebuffer() {
+            Some(framebuffer) => match &mut self.framebuffer.texture {
+                Some(bound) if bound.version == framebuffer.version() => {
+                    if framebuffer.is_dirty() {
+                        let pixels = framebuffer.pixels();
+                        bound.write(&self.queue, pixels, TexelRect::of(pixels));
+                    }
+                }
+                texture => {
+                    *texture = Some(BoundTexture::new(
+                        &self.device,
+                        &self.queue,
+                        &self.layouts,
+                        framebuffer.pixels(),
+                        &framebuffer.options(),
+                        framebuffer.version(),
+                        Some("framebuffer"),
+                    ));
+                }
+            },
+            None => self.framebuffer.texture = None,
+        }
+
+        // Only the ranges of the canvas buffers that changed are written
+        let dirty = canvas.dirty_ranges();
+        let vertices = canvas.vertices();
+        let vertex_buffer = &mut self.buffers.vertex_buffer;
+        for range in vertex_buffer.prepare(&self.device, vertices.len(), &dirty.vertices) {
+            vertex_buffer.write(
+                &self.queue,
+                range.start,
+                bytemuck::cast_slice(&vertices[range]),
+            );
+        }
+        let indices = canvas.indices();
+        let index_buffer = &mut self.buffers.index_buffer;
+        for range in index_buffer.prepare(&self.device, indices.len(), &dirty.indices) {
+            index_buffer.write(
+                &self.queue,
+                range.start,
+                bytemuck::cast_slice(&indices[range]),
+            );
+        }
+        let transforms = canvas.transforms();
+        let transform_buffer = &mut self.buffers.transform_buffer;
+        let capacity = transform_buffer.capacity;
+        for range in transform_buffer.prepare(&self.device, transforms.len(), &dirty.transforms) {
+            // Uploaded as they are, the vertex shader builds the model matrices
+            transform_buffer.write(
+                &self.queue,
+                range.start,
+                bytemuck::cast_slice(&transforms[range]),
+            );
+        }
+        if transform_buffer.capacity != capacity {
+            self.bindings.transform_bind_group = self
+                .layouts
+                .transform_bind_group(&self.device, &transform_buffer.buffer);
+        }
+        canvas.clear_updates();
+    }
+
+    /// Records a render pass that draws the uploaded canvas data into the view of the size,
+    /// the framebuffer first and then one draw call per batch of meshes that share a texture.
+    fn encode_pass(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        canvas: &Canvas,
+        (width, height): (u32, u32),
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                attachment: view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline.render_pipeline);
+        if let (Some(framebuffer), Some(bound)) = (canvas.framebuffer(), &self.framebuffer.texture)
+        {
+            let viewport = framebuffer.viewport(width, height);
+            render_pass.set_viewport(
+                viewport.min[0],
+                viewport.min[1],
+                viewport.width(),
+                viewport.height(),
+                0.0,
+                1.0,
+            );
+            render_pass.set_bind_group(0, bound.bind_group(), &[]);
+            render_pass.set_bind_group(1, &self.framebuffer.uniform_bind_group, &[]);
+            render_pass.set_bind_group(2, &self.framebuffer.transform_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, self.framebuffer.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(self.framebuffer.index_buffer.slice(..));
+            render_pass.draw_indexed(0..QUAD_INDICES.len() as u32, 0, 0..1);
+        }
+        let viewport = self.bindings.viewport;
+        render_pass.set_viewport(
+            viewport.min[0],
+            viewport.min[1],
+            viewport.width(),
+            viewport.height(),
+            0.0,
+            1.0,
+        );
+        render_pass.set_bind_group(1, &self.bindings.uniform_bind_group, &[]);
+        render_pass.set_bind_group(2, &self.bindings.transform_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.buffers.vertex_buffer.buffer.slice(..));
+        render_pass.set_index_buffer(self.buffers.index_buffer.buffer.slice(..));
+        for (indices, texture) in canvas.batches() {
+            let bound = self.bindings.texture(texture);
+            render_pass.set_bind_group(0, bound.bind_group(), &[]);
+            render_pass.draw_indexed(indices, 0, 0..1);
+        }
+    }
+
+    fn target_size(&self) -> (u32, u32) {
+        match &self.target {
+            Target::Surface(onscreen) => (
+                onscreen.swap_chain_descriptor.width,
+                onscreen.swap_chain_descriptor.height,
+            ),
+            Target::Offscreen(offscreen) => (offscreen.width, offscreen.height),
         }
-        self.queue.submit(Some(encoder.finish()));
     }
 
+    /// Resizes the target, a surface gets a new swap chain unless it has no area.
     pub fn resize(&mut self, width: u32, height: u32) {
-        self.pipeline.swap_chain_descriptor.width = width;
-        self.pipeline.swap_chain_descriptor.height = height;
+        match &mut self.target {
+            Target::Surface(onscreen) => {
+                onscreen.swap_chain_descriptor.width = width;
+                onscreen.swap_chain_descriptor.height = height;
+                onscreen.recreate(&self.device);
+            }
+            Target::Offscreen(offscreen) => {
+                *offscreen = Offscreen::new(&self.device, width, height);
+            }
+        }
     }
 }
 
@@ -212,46 +614,47 @@ impl Layouts {
             pipeline_layout,
         }
     }
+
+    /// Binds the buffer as the transforms that vertices look up their model matrix in.
+    fn transform_bind_group(
+        &self,
+        device: &wgpu::Device,
+        buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.transform_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(buffer.slice(..)),
+            }],
+            label: Some("transform_bind_group"),
+        })
+    }
 }
 
 impl Bindings {
-    fn new(device: &wgpu::Device, queue: &wgpu::Queue, layouts: &Layouts) -> Self {
-        let diffuse_bytes = include_bytes!("../images/happy-tree.png");
-        let diffuse_texture =
-            Texture::from_bytes(&device, &queue, diffuse_bytes, "happy-tree.png").unwrap();
-
-        let diffuse_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &layouts.diffuse_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
-                },
-            ],
-            label: Some("diffuse_bind_group"),
-        });
+    fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layouts: &Layouts,
+        buffers: &Buffers,
+    ) -> Self {
+        // Meshes without a texture sample a white pixel so they show their color
+        let white = image::RgbaImage::from_pixel(1, 1, image::Rgba([255, 255, 255, 255]));
+        let white = BoundTexture::new(
+            device,
+            queue,
+            layouts,
+            &white,
+            &TextureOptions::default(),
+            0,
+            Some("white"),
+        );
+        let textures = vec![Some(white)];
 
-        let camera = Camera {
-            eye: [0.0, 0.0, 1.0],
-            target: [0.0, 0.0, 0.0],
-            up: [0.0, 1.0, 0.0],
-            projection: Projection::Orthographic {
-                left: -1.0,
-                right: 1.0,
-                bottom: -1.0,
-                top: 1.0,
-                near: -1.0,
-                far: 2.0,
-            },
-        };
-        let mut uniforms = Uniform::new();
-        uniforms.update_view_proj(&camera);
+        // Written with the camera before the first frame
         let uniform_buffer = device.create_buffer_with_data(
-            bytemuck::cast_slice(&[uniforms]),
+            bytemuck::cast_slice(&[Uniform::new()]),
             wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
         );
 
@@ -263,51 +666,154 @@ impl Bindings {
             }],
             label: Some("uniform_bind_group"),
         });
-        // TODO: dont create this empty transform buffer
-        let transform_buffer = device.create_buffer_with_data(&[], wgpu::BufferUsage::STORAGE);
-        let transform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &layouts.transform_bind_group_layout,
+        let transform_bind_group =
+            layouts.transform_bind_group(device, &buffers.transform_buffer.buffer);
+        Self {
+            canvas: None,
+            textures,
+            view: None,
+            viewport: Rect::default(),
+            uniform_buffer,
+            uniform_bind_group,
+            transform_bind_group,
+        }
+    }
+
+    /// Returns the uploaded texture, or the white pixel if the texture hasn't been uploaded.
+    fn texture(&self, texture: TextureHandle) -> &BoundTexture {
+        self.textures
+            .get(texture.index())
+            .and_then(Option::as_ref)
+            .or_else(|| self.textures[TextureHandle::WHITE.index()].as_ref())
+            .expect("The white texture is always bound!")
+    }
+}
+
+impl FramebufferQuad {
+    fn new(device: &wgpu::Device, layouts: &Layouts) -> Self {
+        let corners = [[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
+        let tex_coords = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+        let vertices = corners
+            .iter()
+            .zip(&tex_coords)
+            .map(|([x, y], tex_coords)| Vertex {
+                position: [*x, *y, 0.0],
+                color: [1.0, 1.0, 1.0, 1.0],
+                tex_coords: *tex_coords,
+                mix_factor: 1.0,
+                transform_index: 0,
+            })
+            .collect::<Vec<_>>();
+        let vertex_buffer = device
+            .create_buffer_with_data(bytemuck::cast_slice(&vertices), wgpu::BufferUsage::VERTEX);
+        let index_buffer = device.create_buffer_with_data(
+            bytemuck::cast_slice(&QUAD_INDICES),
+            wgpu::BufferUsage::INDEX,
+        );
+
+        let uniform_buffer = device.create_buffer_with_data(
+            bytemuck::cast_slice(&[Uniform::new()]),
+            wgpu::BufferUsage::UNIFORM,
+        );
+        let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layouts.uniform_bind_group_layout,
             entries: &[wgpu::BindGroupEntry {
                 binding: 0,
-                resource: wgpu::BindingResource::Buffer(transform_buffer.slice(..)),
+                resource: wgpu::BindingResource::Buffer(uniform_buffer.slice(..)),
             }],
-            label: Some("transform_bind_group"),
+            label: Some("framebuffer_uniform_bind_group"),
         });
+        let transform_buffer = device.create_buffer_with_data(
+            bytemuck::cast_slice(&[Transform::default()]),
+            wgpu::BufferUsage::STORAGE,
+        );
+        let transform_bind_group = layouts.transform_bind_group(device, &transform_buffer);
         Self {
-            diffuse_bind_group,
+            texture: None,
+            vertex_buffer,
+            index_buffer,
             uniform_bind_group,
             transform_bind_group,
         }
     }
 }
 
-impl Pipeline {
+impl BoundTexture {
     fn new(
         device: &wgpu::Device,
-        surface: &wgpu::Surface,
+        queue: &wgpu::Queue,
         layouts: &Layouts,
-        width: u32,
-        height: u32,
+        image: &image::RgbaImage,
+        options: &TextureOptions,
+        version: u64,
+        label: Option<&str>,
     ) -> Self {
-        let swap_chain_descriptor = wgpu::SwapChainDescriptor {
-            usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
-            format: wgpu::TextureFormat::Bgra8UnormSrgb,
-            width,
-            height,
-            present_mode: wgpu::PresentMode::Mailbox,
+        let copies = if options.double_buffered { 2 } else { 1 };
+        let buffers = (0..copies)
+            .map(|_| {
+                let texture = Texture::from_rgba(device, queue, image, options, label);
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &layouts.diffuse_bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&texture.view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                        },
+                    ],
+                    label: Some("diffuse_bind_group"),
+                });
+                (texture, bind_group)
+            })
+            .collect();
+        Self {
+            buffers,
+            front: 0,
+            stale: None,
+            version,
+        }
+    }
+
+    fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.buffers[self.front].1
+    }
+
+    /// Writes the rectangle of the image, which the texture was created from, into the texture.
+    fn write(&mut self, queue: &wgpu::Queue, image: &image::RgbaImage, rect: TexelRect) {
+        if self.buffers.len() == 1 {
+            self.buffers[0].0.write_rect(queue, image, rect);
+            return;
+        }
+        // The back copy also misses the update that was written to the front copy
+        let back = 1 - self.front;
+        let missing = match self.stale.replace(rect) {
+            Some(stale) => stale.union(rect),
+            None => rect,
         };
-        let swap_chain = device.create_swap_chain(&surface, &swap_chain_descriptor);
+        self.buffers[back].0.write_rect(queue, image, missing);
+        self.front = back;
+    }
+}
 
+impl Pipeline {
+    fn new(device: &wgpu::Device, layouts: &Layouts, format: wgpu::TextureFormat) -> Self {
         let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             layout: &layouts.pipeline_layout,
             vertex_stage: wgpu::ProgrammableStageDescriptor {
-                module: &device
-                    .create_shader_module(wgpu::include_spirv!("shader/shader.vert.spv")),
+                module: &device.create_shader_module(wgpu::include_spirv!(concat!(
+                    env!("OUT_DIR"),
+                    "/shader.vert.spv"
+                ))),
                 entry_point: "main",
             },
             fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
-                module: &device
-                    .create_shader_module(wgpu::include_spirv!("shader/shader.frag.spv")),
+                module: &device.create_shader_module(wgpu::include_spirv!(concat!(
+                    env!("OUT_DIR"),
+                    "/shader.frag.spv"
+                ))),
                 entry_point: "main",
             }),
             rasterization_state: Some(wgpu::RasterizationStateDescriptor {
@@ -317,9 +823,17 @@ impl Pipeline {
             }),
             primitive_topology: wgpu::PrimitiveTopology::TriangleList,
             color_states: &[wgpu::ColorStateDescriptor {
-                format: swap_chain_descriptor.format,
-                color_blend: wgpu::BlendDescriptor::REPLACE,
-                alpha_blend: wgpu::BlendDescriptor::REPLACE,
+                format,
+                color_blend: wgpu::BlendDescriptor {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha_blend: wgpu::BlendDescriptor {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                    operation: wgpu::BlendOperation::Add,
+                },
                 write_mask: wgpu::ColorWrite::ALL,
             }],
             depth_stencil_state: None,
@@ -332,21 +846,220 @@ impl Pipeline {
             alpha_to_coverage_enabled: false,
         });
 
+        Self { render_pipeline }
+    }
+}
+
+impl Target {
+    fn format(&self) -> wgpu::TextureFormat {
+        match self {
+            Target::Surface(_) => SURFACE_FORMAT,
+            Target::Offscreen(_) => OFFSCREEN_FORMAT,
+        }
+    }
+}
+
+impl Onscreen {
+    /// Creates a new swap chain with the descriptor, or none if its size is zero.
+    fn recreate(&mut self, device: &wgpu::Device) {
+        // Drop the old swap chain first, only one can exist for a surface at a time
+        self.swap_chain = None;
+        let descriptor = &self.swap_chain_descriptor;
+        if has_area(descriptor) {
+            self.swap_chain = Some(device.create_swap_chain(&self.surface, descriptor));
+        }
+    }
+}
+
+/// Returns the ranges of a buffer with `len` elements to write, given the sorted ranges
+/// that changed and the number of elements written before.
+fn written_ranges(dirty: &[Range<usize>], written: usize, len: usize) -> Vec<Range<usize>> {
+    let mut ranges = dirty
+        .iter()
+        .map(|range| range.start.min(len)..range.end.min(len))
+        .filter(|range| range.start < range.end)
+        .collect::<Vec<_>>();
+    if written < len {
+        // New elements are written together with the changed ranges they touch
+        let start = ranges
+            .iter()
+            .filter(|range| range.end >= written)
+            .map(|range| range.start)
+            .fold(written, usize::min);
+        ranges.retain(|range| range.end < written);
+        ranges.push(start..len);
+    }
+    ranges
+}
+
+/// Returns false for swap chains of minimized windows, which can't be created.
+fn has_area(descriptor: &wgpu::SwapChainDescriptor) -> bool {
+    descriptor.width > 0 && descriptor.height > 0
+}
+
+/// Decides how to continue after failing to acquire the next frame.
+fn recovery(error: wgpu::SwapChainError) -> Recovery {
+    match error {
+        wgpu::SwapChainError::Timeout => Recovery::Skip,
+        wgpu::SwapChainError::Outdated | wgpu::SwapChainError::Lost => Recovery::RecreateSwapChain,
+        wgpu::SwapChainError::OutOfMemory => Recovery::ReleaseSwapChain,
+    }
+}
+
+impl Offscreen {
+    fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("offscreen_texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: OFFSCREEN_FORMAT,
+            usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::COPY_SRC,
+        });
+        let view = texture.create_default_view();
         Self {
-            swap_chain_descriptor,
-            swap_chain,
-            render_pipeline,
+            texture,
+            view,
+            width,
+            height,
         }
     }
 }
 
 impl Buffers {
     fn new(device: &wgpu::Device) -> Self {
-        let vertex_buffer = device.create_buffer_with_data(&[], wgpu::BufferUsage::VERTEX);
-        let index_buffer = device.create_buffer_with_data(&[], wgpu::BufferUsage::INDEX);
         Self {
-            vertex_buffer,
-            index_buffer,
+            vertex_buffer: GrowableBuffer::new::<Vertex>(
+                device,
+                wgpu::BufferUsage::VERTEX,
+                "vertex_buffer",
+            ),
+            index_buffer: GrowableBuffer::new::<u32>(
+                device,
+                wgpu::BufferUsage::INDEX,
+                "index_buffer",
+            ),
+            transform_buffer: GrowableBuffer::new::<Transform>(
+                device,
+                wgpu::BufferUsage::STORAGE,
+                "transform_buffer",
+            ),
         }
     }
 }
+
+impl GrowableBuffer {
+    fn new<T>(device: &wgpu::Device, usage: wgpu::BufferUsage, label: &'static str) -> Self {
+        let stride = std::mem::size_of::<T>();
+        Self {
+            buffer: Self::create(device, usage, label, stride * MIN_BUFFER_CAPACITY),
+            usage,
+            label,
+            stride,
+            capacity: MIN_BUFFER_CAPACITY,
+            len: 0,
+        }
+    }
+
+    fn create(
+        device: &wgpu::Device,
+        usage: wgpu::BufferUsage,
+        label: &str,
+        size: usize,
+    ) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: size as wgpu::BufferAddress,
+            usage: usage | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Makes room for `len` elements and returns the ranges of them that have to be written,
+    /// given the sorted ranges that changed since the last write.
+    ///
+    /// Growing recreates the buffer so all elements have to be written,
+    /// as do elements past the end of the last write.
+    fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        len: usize,
+        dirty: &[Range<usize>],
+    ) -> Vec<Range<usize>> {
+        if len > self.capacity {
+            self.capacity = len.next_power_of_two();
+            self.buffer = Self::create(device, self.usage, self.label, self.stride * self.capacity);
+            self.len = len;
+            return vec![0..len];
+        }
+        let ranges = written_ranges(dirty, self.len, len);
+        self.len = len;
+        ranges
+    }
+
+    /// Forgets what was written, so the next write covers all elements.
+    fn invalidate(&mut self) {
+        self.len = 0;
+    }
+
+    /// Writes the bytes of consecutive elements starting at the element with the index.
+    fn write(&self, queue: &wgpu::Queue, start: usize, bytes: &[u8]) {
+        queue.write_buffer(
+            &self.buffer,
+            (start * self.stride) as wgpu::BufferAddress,
+            bytes,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minimized_windows_have_no_swap_chain() {
+        let descriptor = |width, height| wgpu::SwapChainDescriptor {
+            usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+            format: SURFACE_FORMAT,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::Mailbox,
+        };
+        assert!(has_area(&descriptor(800, 600)));
+        assert!(!has_area(&descriptor(0, 0)));
+        assert!(!has_area(&descriptor(800, 0)));
+        assert!(!has_area(&descriptor(0, 600)));
+    }
+
+    #[test]
+    fn writes_changed_and_new_elements() {
+        assert_eq!(written_ranges(&[2..4, 8..10], 10, 10), vec![2..4, 8..10]);
+        assert_eq!(written_ranges(&[2..4, 8..10], 10, 9), vec![2..4, 8..9]);
+        assert_eq!(written_ranges(&[2..4, 8..10], 9, 12), vec![2..4, 8..12]);
+        assert_eq!(written_ranges(&[2..4], 6, 8), vec![2..4, 6..8]);
+        assert_eq!(written_ranges(&[], 0, 5), vec![0..5]);
+        assert!(written_ranges(&[], 5, 5).is_empty());
+    }
+
+    #[test]
+    fn recovers_from_swap_chain_errors() {
+        assert_eq!(recovery(wgpu::SwapChainError::Timeout), Recovery::Skip);
+        assert_eq!(
+            recovery(wgpu::SwapChainError::Outdated),
+            Recovery::RecreateSwapChain
+        );
+        assert_eq!(
+            recovery(wgpu::SwapChainError::Lost),
+            Recovery::RecreateSwapChain
+        );
+        assert_eq!(
+            recovery(wgpu::SwapChainError::OutOfMemory),
+            Recovery::ReleaseSwapChain
+        );
+    }
+}
@@ -10,5 +10,6 @@ layout(set = 0, binding = 0) uniform texture2D t_diffuse;
 layout(set = 0, binding = 1) uniform sampler s_diffuse;
 
 void main() {
-    f_color = mix(v_color, texture(sampler2D(t_diffuse, s_diffuse), v_tex_coords), v_mix_factor);
-}
\ No newline at end of file
+    vec4 texel = texture(sampler2D(t_diffuse, s_diffuse), v_tex_coords);
+    f_color = mix(v_color, v_color * texel, v_mix_factor);
+}
@@ -12,11 +12,44 @@ layout(location=2) out float v_mix_factor;
 
 layout(set=1, binding=0) uniform Uniforms { mat4 u_view_proj; };
 
-layout(set=2, binding=0) buffer Instances { mat4 s_models[]; };
+// Transforms packed as 13 floats each:
+// translation xyz, rotation (scalar first), scale xyz and pivot xyz
+layout(set=2, binding=0) buffer Transforms { float s_transforms[]; };
+
+// Translation * pivot * rotation * scale * -pivot, the same as Transform::to_matrix on the CPU
+mat4 model_matrix(uint index) {
+    uint i = index * 13;
+    vec3 translation = vec3(s_transforms[i], s_transforms[i + 1], s_transforms[i + 2]);
+    float s = s_transforms[i + 3];
+    vec3 v = vec3(s_transforms[i + 4], s_transforms[i + 5], s_transforms[i + 6]);
+    vec3 scale = vec3(s_transforms[i + 7], s_transforms[i + 8], s_transforms[i + 9]);
+    vec3 pivot = vec3(s_transforms[i + 10], s_transforms[i + 11], s_transforms[i + 12]);
+    vec3 v2 = v * 2.0;
+    float xx2 = v2.x * v.x;
+    float yy2 = v2.y * v.y;
+    float zz2 = v2.z * v.z;
+    float xy2 = v2.x * v.y;
+    float xz2 = v2.x * v.z;
+    float yz2 = v2.y * v.z;
+    float sx2 = v2.x * s;
+    float sy2 = v2.y * s;
+    float sz2 = v2.z * s;
+    mat3 rotation_scale = mat3(
+        vec3(1.0 - yy2 - zz2, xy2 + sz2, xz2 - sy2) * scale.x,
+        vec3(xy2 - sz2, 1.0 - xx2 - zz2, yz2 + sx2) * scale.y,
+        vec3(xz2 + sy2, yz2 - sx2, 1.0 - xx2 - yy2) * scale.z
+    );
+    return mat4(
+        vec4(rotation_scale[0], 0.0),
+        vec4(rotation_scale[1], 0.0),
+        vec4(rotation_scale[2], 0.0),
+        vec4(translation + pivot - rotation_scale * pivot, 1.0)
+    );
+}
 
 void main() {
     v_tex_coords = a_tex_coords;
     v_color = a_color;
     v_mix_factor = a_mix_factor;
-    gl_Position = u_view_proj * s_models[a_instance_id] * vec4(a_position, 1.0);
+    gl_Position = u_view_proj * model_matrix(a_instance_id) * vec4(a_position, 1.0);
 }
\ No newline at end of file
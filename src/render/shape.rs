@@ -0,0 +1,400 @@
+//! Tessellation of 2D shapes into triangles.
+//!
+//! Every function returns a [`MeshBuilder`] centered on the local origin with counter-clockwise
+//! triangles, ready to be added to a [`Canvas`].
+//! Curved outlines are split into straight segments so that no point on a segment is further
+//! than `tolerance` from the true curve.
+//!
+//! [`MeshBuilder`]: ../mesh/struct.MeshBuilder.html
+//! [`Canvas`]: ../mesh/struct.Canvas.html
+use super::error::Error;
+use super::math::*;
+use super::mesh::MeshBuilder;
+use std::f32::consts::PI;
+
+/// Upper bound on the segments of a single curve, guards against a tolerance of zero.
+pub(crate) const MAX_SEGMENTS: usize = 4096;
+
+/// Number of segments needed to approximate an arc of the radius and sweep angle in radians.
+///
+/// A tolerance of zero or less gives the most segments allowed.
+pub fn segments(radius: f32, sweep: f32, tolerance: f32) -> usize {
+    let radius = radius.abs();
+    let sweep = sweep.abs();
+    let tolerance = tolerance.max(0.0);
+    if radius <= tolerance {
+        return 1;
+    }
+    // The largest angle a chord can span while staying within tolerance of the arc
+    let step = 2.0 * (1.0 - tolerance / radius).acos();
+    ((sweep / step).ceil() as usize).max(1).min(MAX_SEGMENTS)
+}
+
+/// Points along the elliptical arc from `start` to `start + sweep` radians, inclusive.
+pub fn arc_points(rx: f32, ry: f32, start: f32, sweep: f32, tolerance: f32) -> Vec<Vec2> {
+    let n = segments(rx.abs().max(ry.abs()), sweep, tolerance);
+    (0..=n)
+        .map(|i| {
+            let angle = start + sweep * i as f32 / n as f32;
+            [rx * angle.cos(), ry * angle.sin()]
+        })
+        .collect()
+}
+
+pub fn circle(radius: f32, tolerance: f32) -> MeshBuilder {
+    ellipse(radius, radius, tolerance)
+}
+
+/// An ellipse with the radii along x and y, their signs are ignored.
+pub fn ellipse(rx: f32, ry: f32, tolerance: f32) -> MeshBuilder {
+    // A negative radius would mirror the outline and turn the triangles clockwise
+    let (rx, ry) = (rx.abs(), ry.abs());
+    let n = segments(rx.abs().max(ry.abs()), 2.0 * PI, tolerance).max(3);
+    let outline = (0..n)
+        .map(|i| {
+            let angle = 2.0 * PI * i as f32 / n as f32;
+            [rx * angle.cos(), ry * angle.sin()]
+        })
+        .collect::<Vec<_>>();
+    fan(&outline)
+}
+
+/// A pie slice of the circle between the angles in radians, measured counter-clockwise from +x.
+///
+/// The slice goes counter-clockwise from `start` to `end`, wrapping past +x if `end` is smaller.
+pub fn pie(radius: f32, start: f32, end: f32, tolerance: f32) -> MeshBuilder {
+    let (start, sweep) = sweep(start, end);
+    let mut positions = vec![[0.0, 0.0, 0.0]];
+    positions.extend(
+        arc_points(radius, radius, start, sweep, tolerance)
+            .into_iter()
+            .map(|[x, y]| [x, y, 0.0]),
+    );
+    let indices = (1..positions.len() as u32 - 1)
+        .flat_map(|i| vec![0, i, i + 1])
+        .collect();
+    MeshBuilder::new(positions, indices)
+}
+
+/// A ring segment between the inner and outer radius and the angles in radians,
+/// measured counter-clockwise from +x.
+///
+/// Like [`pie`](fn.pie.html) the segment always turns counter-clockwise from `start` to `end`.
+/// The signs of the radii are ignored and they are swapped if the inner radius is larger.
+pub fn arc(inner: f32, outer: f32, start: f32, end: f32, tolerance: f32) -> MeshBuilder {
+    let (inner, outer) = (inner.abs().min(outer.abs()), inner.abs().max(outer.abs()));
+    let (start, sweep) = sweep(start, end);
+    let outer_points = arc_points(outer, outer, start, sweep, tolerance);
+    let n = outer_points.len() as u32;
+    let positions = outer_points
+        .iter()
+        .enumerate()
+        .flat_map(|(i, [x, y])| {
+            let angle = start + sweep * i as f32 / (n - 1) as f32;
+            vec![
+                [inner * angle.cos(), inner * angle.sin(), 0.0],
+                [*x, *y, 0.0],
+            ]
+        })
+        .collect();
+    let indices = (0..n - 1)
+        .flat_map(|i| {
+            let inner0 = 2 * i;
+            let outer0 = inner0 + 1;
+            let inner1 = inner0 + 2;
+            let outer1 = inner0 + 3;
+            vec![inner0, outer0, outer1, inner0, outer1, inner1]
+        })
+        .collect();
+    MeshBuilder::new(positions, indices)
+}
+
+/// A rectangle with its corners rounded by the radius, which is clamped to fit the rectangle.
+pub fn rounded_rect(w: f32, h: f32, radius: f32, tolerance: f32) -> MeshBuilder {
+    let radius = radius.max(0.0).min(w.abs() * 0.5).min(h.abs() * 0.5);
+    let x = w.abs() * 0.5 - radius;
+    let y = h.abs() * 0.5 - radius;
+    let corners = [[x, -y], [x, y], [-x, y], [-x, -y]];
+    let mut outline = Vec::new();
+    for (i, [cx, cy]) in corners.iter().enumerate() {
+        let start = -0.5 * PI + i as f32 * 0.5 * PI;
+        outline.extend(
+            arc_points(radius, radius, start, 0.5 * PI, tolerance)
+                .into_iter()
+                .map(|[px, py]| [cx + px, cy + py]),
+        );
+    }
+    fan(&outline)
+}
+
+/// A simple polygon, which may be concave, with its points in either winding order.
+///
+/// The polygon is triangulated by ear clipping, polygons with crossing edges are rejected.
+pub fn polygon(points: &[Vec2]) -> Result<MeshBuilder, Error> {
+    let indices = triangulate(points)?;
+    let positions = points.iter().map(|[x, y]| [*x, *y, 0.0]).collect();
+    Ok(MeshBuilder::new(positions, indices))
+}
+
+/// Triangulates a simple polygon into counter-clockwise triangles by ear clipping.
+pub fn triangulate(points: &[Vec2]) -> Result<Vec<u32>, Error> {
+    let n = points.len();
+    if n < 3 {
+        return Err(Error::PolygonPoints(n));
+    }
+    if edges_cross(points) {
+        return Err(Error::PolygonNotSimple);
+    }
+    let mut remaining = if signed_area(points) >= 0.0 {
+        (0..n).collect::<Vec<_>>()
+    } else {
+        (0..n).rev().collect::<Vec<_>>()
+    };
+    let mut indices = Vec::with_capacity((n - 2) * 3);
+    while remaining.len() > 3 {
+        let m = remaining.len();
+        let corner = |i: usize| {
+            (
+                remaining[(i + m - 1) % m],
+                remaining[i],
+                remaining[(i + 1) % m],
+            )
+        };
+        let ear = (0..m)
+            .find(|i| {
+                let (a, b, c) = corner(*i);
+                cross(points[a], points[b], points[c]) > 0.0
+                    && !remaining.iter().any(|p| {
+                        *p != a
+                            && *p != b
+                            && *p != c
+                            && in_triangle(points[*p], points[a], points[b], points[c])
+                    })
+            })
+            // Points on a straight line can always be clipped, they only produce empty triangles
+            .or_else(|| {
+                (0..m).find(|i| {
+                    let (a, b, c) = corner(*i);
+                    cross(points[a], points[b], points[c]).abs() <= f32::EPSILON
+                })
+            })
+            .ok_or(Error::PolygonNotSimple)?;
+        let (a, b, c) = corner(ear);
+        indices.extend_from_slice(&[a as u32, b as u32, c as u32]);
+        remaining.remove(ear);
+    }
+    indices.extend(remaining.iter().map(|i| *i as u32));
+    Ok(indices)
+}
+
+/// Signed area of the polygon, positive when its points are counter-clockwise.
+pub fn signed_area(points: &[Vec2]) -> f32 {
+    let n = points.len();
+    (0..n)
+        .map(|i| {
+            let [x0, y0] = points[i];
+            let [x1, y1] = points[(i + 1) % n];
+            x0 * y1 - x1 * y0
+        })
+        .sum::<f32>()
+        * 0.5
+}
+
+/// Collects triangles with shared vertices, fixing up the winding of each triangle so it faces the camera.
+#[derive(Default)]
+pub(crate) struct Triangles {
+    positions: Vec<Vec3>,
+    indices: Vec<u32>,
+}
+
+impl Triangles {
+    pub fn vertex(&mut self, [x, y]: Vec2) -> u32 {
+        self.positions.push([x, y, 0.0]);
+        self.positions.len() as u32 - 1
+    }
+
+    pub fn triangle(&mut self, a: u32, b: u32, c: u32) {
+        let point = |i: u32| {
+            let [x, y, _] = self.positions[i as usize];
+            [x, y]
+        };
+        if cross(point(a), point(b), point(c)) >= 0.0 {
+            self.indices.extend_from_slice(&[a, b, c]);
+        } else {
+            self.indices.extend_from_slice(&[a, c, b]);
+        }
+    }
+
+    pub fn quad(&mut self, a: u32, b: u32, c: u32, d: u32) {
+        self.triangle(a, b, c);
+        self.triangle(a, c, d);
+    }
+
+    /// Adds a fan of triangles around the center through the points along an arc.
+    pub fn fan(&mut self, center: Vec2, points: &[Vec2]) {
+        let c = self.vertex(center);
+        let outline = points.iter().map(|p| self.vertex(*p)).collect::<Vec<_>>();
+        for pair in outline.windows(2) {
+            self.triangle(c, pair[0], pair[1]);
+        }
+    }
+
+    pub fn build(self) -> MeshBuilder {
+        MeshBuilder::new(self.positions, self.indices)
+    }
+}
+
+/// Triangulates a convex outline as a fan around its first point.
+fn fan(outline: &[Vec2]) -> MeshBuilder {
+    let positions = outline.iter().map(|[x, y]| [*x, *y, 0.0]).collect();
+    let indices = (1..outline.len() as u32 - 1)
+        .flat_map(|i| vec![0, i, i + 1])
+        .collect();
+    MeshBuilder::new(positions, indices)
+}
+
+/// Returns the counter-clockwise sweep from `start` to `end`, at most one turn.
+///
+/// An `end` before `start` is a turn later, so the sweep wraps around instead of reversing.
+fn sweep(start: f32, end: f32) -> (f32, f32) {
+    let sweep = end - start;
+    if sweep >= 2.0 * PI {
+        (start, 2.0 * PI)
+    } else {
+        (start, sweep.rem_euclid(2.0 * PI))
+    }
+}
+
+/// Z component of the cross product of (b - a) and (c - b), positive for a left turn.
+fn cross(a: Vec2, b: Vec2, c: Vec2) -> f32 {
+    (b[0] - a[0]) * (c[1] - b[1]) - (b[1] - a[1]) * (c[0] - b[0])
+}
+
+/// Returns true if two edges of the polygon cross each other, which takes quadratic time.
+///
+/// Edges that only touch are left to the ear clipping, which fails if they make the polygon not simple.
+fn edges_cross(points: &[Vec2]) -> bool {
+    let n = points.len();
+    let edge = |i: usize| (points[i], points[(i + 1) % n]);
+    (0..n).any(|i| {
+        // Neighbouring edges share a point, the last edge neighbours the first
+        (i + 2..n).filter(|j| !(i == 0 && *j == n - 1)).any(|j| {
+            let (a, b) = edge(i);
+            let (c, d) = edge(j);
+            let opposite = |s: f32, t: f32| (s > 0.0 && t < 0.0) || (s < 0.0 && t > 0.0);
+            opposite(cross(c, d, a), cross(c, d, b)) && opposite(cross(a, b, c), cross(a, b, d))
+        })
+    })
+}
+
+fn in_triangle(p: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    let edge = |a: Vec2, b: Vec2| (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0]);
+    edge(a, b) >= 0.0 && edge(b, c) >= 0.0 && edge(c, a) >= 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(builder: &MeshBuilder) -> Vec<Vec2> {
+        builder
+            .positions()
+            .iter()
+            .map(|[x, y, _]| [*x, *y])
+            .collect()
+    }
+
+    /// Asserts that every triangle faces the camera and none is empty.
+    fn assert_counter_clockwise(points: &[Vec2], indices: &[u32]) {
+        for triangle in indices.chunks(3) {
+            let point = |i: usize| points[triangle[i] as usize];
+            assert!(
+                cross(point(0), point(1), point(2)) > 0.0,
+                "{:?} is not counter-clockwise",
+                triangle
+            );
+        }
+    }
+
+    #[test]
+    fn segments_follow_tolerance() {
+        assert_eq!(segments(1.0, PI, 2.0), 1);
+        assert!(segments(1.0, PI, 0.01) < segments(1.0, PI, 0.001));
+        assert_eq!(segments(1.0, PI, 0.0), MAX_SEGMENTS);
+        assert_eq!(segments(1.0, PI, -0.1), MAX_SEGMENTS);
+        assert_eq!(segments(1.0, PI, f32::NAN), MAX_SEGMENTS);
+    }
+
+    #[test]
+    fn triangulates_concave_polygons() {
+        let arrow = [[0.0, 0.0], [2.0, 1.0], [0.0, 2.0], [0.5, 1.0]];
+        let indices = triangulate(&arrow).unwrap();
+        assert_eq!(indices.len(), 6);
+        assert_counter_clockwise(&arrow, &indices);
+
+        let clockwise = arrow.iter().rev().copied().collect::<Vec<_>>();
+        let indices = triangulate(&clockwise).unwrap();
+        assert_counter_clockwise(&clockwise, &indices);
+    }
+
+    #[test]
+    fn rejects_invalid_polygons() {
+        assert!(matches!(
+            triangulate(&[[0.0, 0.0], [1.0, 0.0]]),
+            Err(Error::PolygonPoints(2))
+        ));
+        let bowtie = [[0.0, 0.0], [1.0, 1.0], [1.0, 0.0], [0.0, 1.0]];
+        assert!(matches!(triangulate(&bowtie), Err(Error::PolygonNotSimple)));
+        // Crossing edges make the polygon not simple even when every corner could be clipped
+        let star = [[0.0, 0.0], [2.0, 0.0], [0.0, 1.0], [1.0, -1.0], [2.0, 1.0]];
+        assert!(matches!(triangulate(&star), Err(Error::PolygonNotSimple)));
+    }
+
+    /// Sums the areas of the triangles.
+    fn area(builder: &MeshBuilder) -> f32 {
+        let points = positions(builder);
+        builder
+            .indices()
+            .chunks(3)
+            .map(|t| {
+                cross(
+                    points[t[0] as usize],
+                    points[t[1] as usize],
+                    points[t[2] as usize],
+                ) * 0.5
+            })
+            .sum()
+    }
+
+    #[test]
+    fn sweeps_wrap_counter_clockwise() {
+        let (start, angle) = sweep(6.1, 0.2);
+        assert_eq!(start, 6.1);
+        assert!((angle - (0.2 + 2.0 * PI - 6.1)).abs() < 1e-5);
+        assert_eq!(sweep(0.0, 1.0), (0.0, 1.0));
+        assert_eq!(sweep(0.0, 3.0 * PI), (0.0, 2.0 * PI));
+        assert_eq!(sweep(-PI, PI), (-PI, 2.0 * PI));
+
+        // The small slice across +x rather than the rest of the circle
+        let slice = pie(1.0, 6.1, 0.2, 0.0001);
+        assert!((area(&slice) - (0.2 + 2.0 * PI - 6.1) * 0.5).abs() < 1e-3);
+        assert_counter_clockwise(&positions(&slice), slice.indices());
+    }
+
+    #[test]
+    fn shapes_are_counter_clockwise() {
+        let shapes = [
+            ellipse(1.0, 0.5, 0.01),
+            ellipse(-1.0, 0.5, 0.01),
+            arc(0.5, 1.0, 0.0, PI, 0.01),
+            arc(1.0, 0.5, 0.0, PI, 0.01),
+            arc(-0.5, 1.0, PI, 0.0, 0.01),
+            pie(1.0, 0.0, 1.5 * PI, 0.01),
+            rounded_rect(2.0, 1.0, 0.25, 0.01),
+        ];
+        for shape in &shapes {
+            assert!(!shape.indices().is_empty());
+            assert_counter_clockwise(&positions(shape), shape.indices());
+        }
+    }
+}
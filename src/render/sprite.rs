@@ -0,0 +1,280 @@
+//! Sprite sheets and frame animations for textured meshes.
+use super::error::Error;
+use super::math::*;
+use super::texture::TextureHandle;
+
+/// A texture divided into frames, such as the poses of a character.
+///
+/// Frames are rectangles in pixels of the texture image, they are numbered in the order
+/// they are added and can optionally be named. Frames that don't fit in the image are
+/// rejected when they are shown.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpriteSheet {
+    texture: TextureHandle,
+    width: u32,
+    height: u32,
+    frames: Vec<Frame>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Frame {
+    name: Option<String>,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl SpriteSheet {
+    /// Creates a sheet without frames for a texture whose image is `width` x `height` pixels,
+    /// sheets are created by [`Canvas::sprite_sheet`] which knows the size.
+    ///
+    /// [`Canvas::sprite_sheet`]: ../mesh/struct.Canvas.html#method.sprite_sheet
+    pub(crate) fn new(texture: TextureHandle, width: u32, height: u32) -> Self {
+        Self {
+            texture,
+            width,
+            height,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Divides the image into a grid of equally sized frames, numbered row by row from the top left.
+    ///
+    /// Partial frames at the right and bottom edges are left out, as is the whole grid
+    /// if the frames are empty.
+    pub fn with_grid(mut self, frame_width: u32, frame_height: u32) -> Self {
+        if frame_width == 0 || frame_height == 0 {
+            return self;
+        }
+        let columns = self.width / frame_width;
+        let rows = self.height / frame_height;
+        for row in 0..rows {
+            for column in 0..columns {
+                self.push(
+                    None,
+                    column * frame_width,
+                    row * frame_height,
+                    frame_width,
+                    frame_height,
+                );
+            }
+        }
+        self
+    }
+
+    /// Adds a frame with its top left corner at (x, y) in pixels.
+    pub fn with_frame(mut self, x: u32, y: u32, width: u32, height: u32) -> Self {
+        self.push(None, x, y, width, height);
+        self
+    }
+
+    /// Adds a frame that can be looked up by name with [`frame`](#method.frame).
+    pub fn with_named_frame(mut self, name: &str, x: u32, y: u32, width: u32, height: u32) -> Self {
+        self.push(Some(name.to_string()), x, y, width, height);
+        self
+    }
+
+    pub fn texture(&self) -> TextureHandle {
+        self.texture
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Returns the number of the first frame with the name.
+    pub fn frame(&self, name: &str) -> Option<usize> {
+        self.frames
+            .iter()
+            .position(|frame| frame.name.as_deref() == Some(name))
+    }
+
+    /// Returns the area of the frame in texture coordinates of the whole image.
+    pub(crate) fn region(&self, index: usize) -> Result<Rect, Error> {
+        let frame = self.frames.get(index).ok_or(Error::FrameOutOfBounds {
+            frame: index,
+            len: self.frames.len(),
+        })?;
+        let fits = |start: u32, length: u32, size: u32| {
+            start.checked_add(length).map_or(false, |end| end <= size)
+        };
+        if !fits(frame.x, frame.width, self.width) || !fits(frame.y, frame.height, self.height) {
+            return Err(Error::FrameOutsideImage {
+                frame: index,
+                x: frame.x,
+                y: frame.y,
+                width: frame.width,
+                height: frame.height,
+                size: (self.width, self.height),
+            });
+        }
+        let (w, h) = (self.width as f32, self.height as f32);
+        Ok(Rect {
+            min: [frame.x as f32 / w, frame.y as f32 / h],
+            max: [
+                (frame.x + frame.width) as f32 / w,
+                (frame.y + frame.height) as f32 / h,
+            ],
+        })
+    }
+
+    fn push(&mut self, name: Option<String>, x: u32, y: u32, width: u32, height: u32) {
+        self.frames.push(Frame {
+            name,
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+}
+
+/// A sequence of sprite sheet frames shown one after another at a fixed rate.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Animation {
+    pub frames: Vec<usize>,
+    /// Number of frames shown per second.
+    pub fps: f32,
+    /// Starts over from the first frame after the last, otherwise the last frame stays.
+    pub looping: bool,
+}
+
+impl Animation {
+    pub fn new(frames: Vec<usize>, fps: f32) -> Self {
+        Self {
+            frames,
+            fps,
+            looping: true,
+        }
+    }
+
+    /// Plays every frame of the sheet in order.
+    pub fn all(sheet: &SpriteSheet, fps: f32) -> Self {
+        Self::new((0..sheet.len()).collect(), fps)
+    }
+
+    pub fn with_looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+}
+
+/// An animation playing on a mesh.
+#[derive(Clone, Debug)]
+pub(crate) struct Playback {
+    /// Region of each frame of the animation in texture coordinates of the texture
+    /// the mesh is drawn with.
+    pub regions: Vec<Rect>,
+    pub fps: f32,
+    pub looping: bool,
+    /// Seconds since the animation started.
+    pub time: f32,
+    pub current: usize,
+}
+
+impl Playback {
+    /// Advances the animation by `dt` seconds and returns the new frame if it changed.
+    ///
+    /// The time is kept within one run of the animation, so it doesn't lose precision
+    /// however long the animation plays.
+    pub fn advance(&mut self, dt: f32) -> Option<usize> {
+        self.time += dt;
+        let duration = self.regions.len() as f32 / self.fps;
+        if duration.is_finite() && duration > 0.0 {
+            self.time = if self.looping {
+                self.time.rem_euclid(duration)
+            } else {
+                self.time.min(duration)
+            };
+        }
+        let frame = (self.time * self.fps).max(0.0) as usize;
+        let frame = if self.looping {
+            frame % self.regions.len()
+        } else {
+            frame.min(self.regions.len() - 1)
+        };
+        if frame == self.current {
+            return None;
+        }
+        self.current = frame;
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn playback(frames: usize, fps: f32, looping: bool) -> Playback {
+        Playback {
+            regions: vec![Rect::default(); frames],
+            fps,
+            looping,
+            time: 0.0,
+            current: 0,
+        }
+    }
+
+    #[test]
+    fn grid_skips_partial_and_empty_frames() {
+        let sheet = SpriteSheet::new(TextureHandle::WHITE, 10, 8).with_grid(4, 4);
+        assert_eq!(sheet.len(), 4);
+        assert_eq!(
+            sheet.region(3).unwrap(),
+            Rect {
+                min: [0.4, 0.5],
+                max: [0.8, 1.0],
+            }
+        );
+        let sheet = SpriteSheet::new(TextureHandle::WHITE, 10, 8).with_grid(0, 4);
+        assert!(sheet.is_empty());
+    }
+
+    #[test]
+    fn frames_must_fit_in_the_image() {
+        let sheet = SpriteSheet::new(TextureHandle::WHITE, 10, 8)
+            .with_frame(6, 0, 4, 8)
+            .with_frame(6, 0, 5, 8)
+            .with_frame(0, 1, 10, 8)
+            .with_frame(u32::MAX, 0, 2, 2)
+            .with_frame(0, 4, 2, u32::MAX);
+        assert!(sheet.region(0).is_ok());
+        for frame in 1..5 {
+            match sheet.region(frame) {
+                Err(Error::FrameOutsideImage { frame: f, .. }) => assert_eq!(f, frame),
+                other => panic!("frame {} is {:?}", frame, other),
+            }
+        }
+        assert!(matches!(
+            sheet.region(5),
+            Err(Error::FrameOutOfBounds { frame: 5, len: 5 })
+        ));
+    }
+
+    #[test]
+    fn looping_time_wraps() {
+        let mut playback = playback(4, 4.0, true);
+        assert_eq!(playback.advance(0.3), Some(1));
+        assert_eq!(playback.advance(0.8), Some(0));
+        assert!(playback.time < 1.0);
+        for _ in 0..100_000 {
+            playback.advance(0.25);
+        }
+        assert!(playback.time < 1.0);
+        assert_eq!(playback.current, 0);
+    }
+
+    #[test]
+    fn finished_time_stops() {
+        let mut playback = playback(4, 4.0, false);
+        assert_eq!(playback.advance(10.0), Some(3));
+        assert_eq!(playback.time, 1.0);
+        assert_eq!(playback.advance(1.0), None);
+        assert_eq!(playback.time, 1.0);
+    }
+}
@@ -0,0 +1,391 @@
+//! Tessellation of stroked lines and polylines into triangles.
+use super::math::*;
+use super::mesh::MeshBuilder;
+use super::shape::{self, Triangles};
+use std::f32::consts::PI;
+
+/// Shape used where two segments of a stroke meet.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LineJoin {
+    /// Extends the outer edges until they meet, falls back to `Bevel` past the miter limit.
+    Miter,
+    /// Rounds the corner with an arc of half the stroke width.
+    Round,
+    /// Cuts the corner off with a straight edge.
+    Bevel,
+}
+
+/// Shape used at the ends of an open stroke.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LineCap {
+    /// Ends the stroke exactly at the end point.
+    Butt,
+    /// Ends the stroke with a half circle around the end point.
+    Round,
+    /// Extends the stroke by half the stroke width past the end point.
+    Square,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StrokeStyle {
+    pub width: f32,
+    pub join: LineJoin,
+    pub cap: LineCap,
+    /// Largest ratio of miter length to stroke width before a miter join is beveled.
+    pub miter_limit: f32,
+}
+
+impl StrokeStyle {
+    pub fn new(width: f32) -> Self {
+        Self {
+            width,
+            join: LineJoin::Miter,
+            cap: LineCap::Butt,
+            miter_limit: 4.0,
+        }
+    }
+
+    pub fn with_join(mut self, join: LineJoin) -> Self {
+        self.join = join;
+        self
+    }
+
+    pub fn with_cap(mut self, cap: LineCap) -> Self {
+        self.cap = cap;
+        self
+    }
+
+    pub fn with_miter_limit(mut self, miter_limit: f32) -> Self {
+        self.miter_limit = miter_limit;
+        self
+    }
+}
+
+/// Tessellates a stroke along the points, closed strokes join the last point back to the first.
+///
+/// Each segment is a quad of its own, so consecutive segments overlap on the inside of
+/// every corner, as does a stroke that crosses itself. Translucent strokes are blended twice
+/// there and look darker, draw them opaque and set the transparency on the whole mesh instead.
+pub fn polyline(points: &[Vec2], closed: bool, style: &StrokeStyle, tolerance: f32) -> MeshBuilder {
+    let mut triangles = Triangles::default();
+    stroke(&mut triangles, points, closed, style, tolerance);
+    triangles.build()
+}
+
+/// Adds the triangles of a stroke along the points to the collection.
+pub(crate) fn stroke(
+    triangles: &mut Triangles,
+    points: &[Vec2],
+    closed: bool,
+    style: &StrokeStyle,
+    tolerance: f32,
+) {
+    let hw = style.width.abs() * 0.5;
+    let mut points = dedup(points);
+    if closed && points.len() > 1 && distance(points[0], points[points.len() - 1]) <= f32::EPSILON {
+        points.pop();
+    }
+    if points.len() < 2 {
+        if let Some(point) = points.first() {
+            dot(triangles, *point, hw, style.cap, tolerance);
+        }
+        return;
+    }
+    let closed = closed && points.len() > 2;
+
+    let n = points.len();
+    let segment_count = if closed { n } else { n - 1 };
+    for i in 0..segment_count {
+        let mut a = points[i];
+        let mut b = points[(i + 1) % n];
+        let d = direction(a, b);
+        if !closed && style.cap == LineCap::Square {
+            if i == 0 {
+                a = sub(a, scale(d, hw));
+            }
+            if i == segment_count - 1 {
+                b = add(b, scale(d, hw));
+            }
+        }
+        let offset = scale(normal(d), hw);
+        let a0 = triangles.vertex(add(a, offset));
+        let a1 = triangles.vertex(sub(a, offset));
+        let b1 = triangles.vertex(sub(b, offset));
+        let b0 = triangles.vertex(add(b, offset));
+        triangles.quad(a0, a1, b1, b0);
+    }
+
+    let joints = if closed { 0..n } else { 1..n - 1 };
+    for i in joints {
+        let prev = points[(i + n - 1) % n];
+        let point = points[i];
+        let next = points[(i + 1) % n];
+        join(triangles, prev, point, next, hw, style, tolerance);
+    }
+
+    if !closed && style.cap == LineCap::Round {
+        let start_direction = direction(points[1], points[0]);
+        let end_direction = direction(points[n - 2], points[n - 1]);
+        round_cap(triangles, points[0], start_direction, hw, tolerance);
+        round_cap(triangles, points[n - 1], end_direction, hw, tolerance);
+    }
+}
+
+/// Fills the gap on the outside of the corner at `point`.
+fn join(
+    triangles: &mut Triangles,
+    prev: Vec2,
+    point: Vec2,
+    next: Vec2,
+    hw: f32,
+    style: &StrokeStyle,
+    tolerance: f32,
+) {
+    let d0 = direction(prev, point);
+    let d1 = direction(point, next);
+    let turn = d0[0] * d1[1] - d0[1] * d1[0];
+    if turn.abs() <= f32::EPSILON {
+        // Straight through the segments already meet. Turning back has no corner to miter
+        // or bevel, only a round join covers the end of the segments
+        if dot_product(d0, d1) > 0.0 || style.join != LineJoin::Round {
+            return;
+        }
+    }
+    // The outside of the corner is on the right of a left turn and the left of a right turn
+    let side = if turn > 0.0 { -1.0 } else { 1.0 };
+    let n0 = scale(normal(d0), side);
+    let n1 = scale(normal(d1), side);
+    let outer0 = add(point, scale(n0, hw));
+    let outer1 = add(point, scale(n1, hw));
+
+    match style.join {
+        LineJoin::Round => {
+            let start = n0[1].atan2(n0[0]);
+            let mut sweep = n1[1].atan2(n1[0]) - start;
+            if sweep > PI {
+                sweep -= 2.0 * PI;
+            } else if sweep < -PI {
+                sweep += 2.0 * PI;
+            }
+            let arc = shape::arc_points(hw, hw, start, sweep, tolerance)
+                .into_iter()
+                .map(|p| add(point, p))
+                .collect::<Vec<_>>();
+            triangles.fan(point, &arc);
+        }
+        LineJoin::Miter => {
+            let bisector = add(n0, n1);
+            let cos_half = dot_product(normalize(bisector), n0);
+            if cos_half > f32::EPSILON && 1.0 / cos_half <= style.miter_limit {
+                let tip = add(point, scale(normalize(bisector), hw / cos_half));
+                let p = triangles.vertex(point);
+                let o0 = triangles.vertex(outer0);
+                let t = triangles.vertex(tip);
+                let o1 = triangles.vertex(outer1);
+                triangles.quad(p, o0, t, o1);
+            } else {
+                bevel(triangles, point, outer0, outer1);
+            }
+        }
+        LineJoin::Bevel => bevel(triangles, point, outer0, outer1),
+    }
+}
+
+fn bevel(triangles: &mut Triangles, point: Vec2, outer0: Vec2, outer1: Vec2) {
+    let p = triangles.vertex(point);
+    let o0 = triangles.vertex(outer0);
+    let o1 = triangles.vertex(outer1);
+    triangles.triangle(p, o0, o1);
+}
+
+/// Adds a half circle at the end point that bulges out in the direction.
+fn round_cap(triangles: &mut Triangles, point: Vec2, direction: Vec2, hw: f32, tolerance: f32) {
+    let n = normal(direction);
+    let start = n[1].atan2(n[0]);
+    let arc = shape::arc_points(hw, hw, start, -PI, tolerance)
+        .into_iter()
+        .map(|p| add(point, p))
+        .collect::<Vec<_>>();
+    triangles.fan(point, &arc);
+}
+
+/// A stroke of zero length is only visible with round or square caps.
+fn dot(triangles: &mut Triangles, point: Vec2, hw: f32, cap: LineCap, tolerance: f32) {
+    match cap {
+        LineCap::Butt => {}
+        LineCap::Round => {
+            let arc = shape::arc_points(hw, hw, 0.0, 2.0 * PI, tolerance)
+                .into_iter()
+                .map(|p| add(point, p))
+                .collect::<Vec<_>>();
+            triangles.fan(point, &arc);
+        }
+        LineCap::Square => {
+            let [x, y] = point;
+            let a = triangles.vertex([x - hw, y - hw]);
+            let b = triangles.vertex([x + hw, y - hw]);
+            let c = triangles.vertex([x + hw, y + hw]);
+            let d = triangles.vertex([x - hw, y + hw]);
+            triangles.quad(a, b, c, d);
+        }
+    }
+}
+
+/// Removes consecutive duplicate points, they have no direction to stroke along.
+fn dedup(points: &[Vec2]) -> Vec<Vec2> {
+    let mut unique: Vec<Vec2> = Vec::with_capacity(points.len());
+    for point in points {
+        if unique
+            .last()
+            .map_or(true, |last| distance(*last, *point) > f32::EPSILON)
+        {
+            unique.push(*point);
+        }
+    }
+    unique
+}
+
+fn add(a: Vec2, b: Vec2) -> Vec2 {
+    [a[0] + b[0], a[1] + b[1]]
+}
+fn sub(a: Vec2, b: Vec2) -> Vec2 {
+    [a[0] - b[0], a[1] - b[1]]
+}
+fn scale(a: Vec2, s: f32) -> Vec2 {
+    [a[0] * s, a[1] * s]
+}
+fn dot_product(a: Vec2, b: Vec2) -> f32 {
+    a[0] * b[0] + a[1] * b[1]
+}
+fn distance(a: Vec2, b: Vec2) -> f32 {
+    let [x, y] = sub(b, a);
+    (x * x + y * y).sqrt()
+}
+fn normalize(a: Vec2) -> Vec2 {
+    let length = (a[0] * a[0] + a[1] * a[1]).sqrt();
+    if length > 0.0 {
+        scale(a, 1.0 / length)
+    } else {
+        a
+    }
+}
+fn direction(from: Vec2, to: Vec2) -> Vec2 {
+    normalize(sub(to, from))
+}
+/// Left hand normal of the direction.
+fn normal(d: Vec2) -> Vec2 {
+    [-d[1], d[0]]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOLERANCE: f32 = 0.0001;
+
+    fn stroke(points: &[Vec2], closed: bool, style: &StrokeStyle) -> (MeshBuilder, f32) {
+        let builder = polyline(points, closed, style, TOLERANCE);
+        let positions = builder.positions();
+        let area = builder
+            .indices()
+            .chunks(3)
+            .map(|triangle| {
+                let point = |i: usize| {
+                    let [x, y, _] = positions[triangle[i] as usize];
+                    [x, y]
+                };
+                let [ax, ay] = sub(point(1), point(0));
+                let [bx, by] = sub(point(2), point(0));
+                let area = (ax * by - ay * bx) * 0.5;
+                assert!(area > 0.0, "{:?} is empty or clockwise", triangle);
+                area
+            })
+            .sum();
+        (builder, area)
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "{} is not close to {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn butt_strokes_cover_the_segment() {
+        let (builder, area) = stroke(&[[0.0, 0.0], [2.0, 0.0]], false, &StrokeStyle::new(0.5));
+        assert_eq!(builder.indices().len(), 6);
+        assert_close(area, 1.0);
+        let ys = builder.positions().iter().map(|[_, y, _]| y.abs());
+        assert!(ys.into_iter().all(|y| y == 0.25));
+    }
+
+    #[test]
+    fn sharp_miters_are_beveled() {
+        // Turns back by all but about 6 degrees, the miter would be 20 times the stroke width
+        let points = [[0.0, 0.0], [1.0, 0.0], [0.0, 0.1]];
+        let (miter, _) = stroke(
+            &points,
+            false,
+            &StrokeStyle::new(0.1).with_miter_limit(100.0),
+        );
+        let (bevel, _) = stroke(&points, false, &StrokeStyle::new(0.1));
+        // Two segment quads and a miter quad or a bevel triangle
+        assert_eq!(miter.indices().len(), 18);
+        assert_eq!(bevel.indices().len(), 15);
+    }
+
+    #[test]
+    fn reversals_have_no_empty_joins() {
+        let points = [[0.0, 0.0], [1.0, 0.0], [0.0, 0.0]];
+        for &join in &[LineJoin::Miter, LineJoin::Bevel] {
+            let (builder, area) = stroke(&points, false, &StrokeStyle::new(0.5).with_join(join));
+            assert_eq!(builder.indices().len(), 12);
+            assert_close(area, 1.0);
+        }
+        let round = StrokeStyle::new(0.5).with_join(LineJoin::Round);
+        let (_, area) = stroke(&points, false, &round);
+        assert_close(area, 1.0 + PI * 0.25 * 0.25 * 0.5);
+    }
+
+    #[test]
+    fn single_points_show_their_cap() {
+        for points in &[vec![[1.0, 1.0]], vec![[1.0, 1.0]; 3]] {
+            let butt = StrokeStyle::new(0.5);
+            let (builder, _) = stroke(points, false, &butt);
+            assert!(builder.indices().is_empty());
+            let (_, area) = stroke(points, false, &butt.with_cap(LineCap::Round));
+            assert_close(area, PI * 0.25 * 0.25);
+            let (_, area) = stroke(points, false, &butt.with_cap(LineCap::Square));
+            assert_close(area, 0.25);
+        }
+    }
+
+    #[test]
+    fn caps_extend_open_strokes() {
+        let points = [[0.0, 0.0], [1.0, 0.0], [1.0, 0.0]];
+        let style = StrokeStyle::new(0.5);
+        let (_, area) = stroke(&points, false, &style.with_cap(LineCap::Square));
+        assert_close(area, 0.5 + 2.0 * 0.25 * 0.5);
+        let (_, area) = stroke(&points, false, &style.with_cap(LineCap::Round));
+        assert_close(area, 0.5 + PI * 0.25 * 0.25);
+    }
+
+    #[test]
+    fn closed_strokes_join_every_corner() {
+        let square = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+        let style = StrokeStyle::new(0.2).with_cap(LineCap::Round);
+        let (builder, area) = stroke(&square, true, &style);
+        // Four segments and four miters, caps are left out
+        assert_eq!(builder.indices().len(), 48);
+        // The 0.8 of the ring, the segments overlap in the inner corners
+        assert_close(area, 0.8 + 4.0 * 0.1 * 0.1);
+
+        let mut repeated = square.to_vec();
+        repeated.push(square[0]);
+        let (repeated, _) = stroke(&repeated, true, &style);
+        assert_eq!(repeated.indices(), builder.indices());
+    }
+}
@@ -0,0 +1,1095 @@
+//! Import of a subset of SVG into a [`Canvas`].
+//!
+//! Supported are `path`, `rect`, `circle`, `ellipse`, `line`, `polyline` and `polygon` elements
+//! inside nested groups with transforms, filled and stroked with solid colors.
+//! Gradients, patterns, text, clipping, masks and `use` references are ignored.
+//!
+//! [`Canvas`]: ../mesh/struct.Canvas.html
+use super::error::Error;
+use super::math::*;
+use super::mesh::{Canvas, Mesh};
+use super::path::{self, FillRule, Path, Polyline};
+use super::stroke::{LineCap, LineJoin, StrokeStyle};
+
+/// A parsed SVG document ready to be added to a [`Canvas`].
+///
+/// [`Canvas`]: ../mesh/struct.Canvas.html
+#[derive(Clone, Debug)]
+pub struct Svg {
+    /// Area of the document that is visible, as (x, y, width, height) in SVG user units.
+    pub view_box: [f32; 4],
+    shapes: Vec<Shape>,
+}
+
+/// The meshes created for a drawable SVG element, in document order.
+#[derive(Clone, Debug)]
+pub struct SvgElement {
+    /// Value of the element's `id` attribute.
+    pub id: Option<String>,
+    pub fill: Option<Mesh>,
+    pub stroke: Option<Mesh>,
+}
+
+#[derive(Clone, Debug)]
+struct Shape {
+    id: Option<String>,
+    path: Path,
+    transform: Transform2d,
+    fill: Option<(Rgba, FillRule)>,
+    stroke: Option<(Rgba, StrokeStyle)>,
+}
+
+/// Affine 2D transform [a, b, c, d, e, f] as in the SVG `matrix` transform.
+type Transform2d = [f32; 6];
+
+const IDENTITY: Transform2d = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// Presentation attributes that are inherited by child elements.
+#[derive(Clone, Debug)]
+struct Style {
+    fill: Option<Rgba>,
+    fill_rule: FillRule,
+    fill_opacity: f32,
+    stroke: Option<Rgba>,
+    stroke_opacity: f32,
+    stroke_width: f32,
+    stroke_join: LineJoin,
+    stroke_cap: LineCap,
+    miter_limit: f32,
+    opacity: f32,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            fill: Some([0.0, 0.0, 0.0, 1.0]),
+            fill_rule: FillRule::NonZero,
+            fill_opacity: 1.0,
+            stroke: None,
+            stroke_opacity: 1.0,
+            stroke_width: 1.0,
+            stroke_join: LineJoin::Miter,
+            stroke_cap: LineCap::Butt,
+            miter_limit: 4.0,
+            opacity: 1.0,
+        }
+    }
+}
+
+impl Svg {
+    /// Parses an SVG document.
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let document = roxmltree::Document::parse(text)?;
+        let root = document.root_element();
+        if root.tag_name().name() != "svg" {
+            return Err(Error::InvalidSvg(format!(
+                "root element is <{}>",
+                root.tag_name().name()
+            )));
+        }
+        let view_box = match root.attribute("viewBox") {
+            Some(view_box) => {
+                let numbers = numbers(view_box)?;
+                if numbers.len() != 4 {
+                    return Err(Error::InvalidSvg(format!("viewBox '{}'", view_box)));
+                }
+                [numbers[0], numbers[1], numbers[2], numbers[3]]
+            }
+            None => [
+                0.0,
+                0.0,
+                length(root.attribute("width"))?.unwrap_or(100.0),
+                length(root.attribute("height"))?.unwrap_or(100.0),
+            ],
+        };
+        let mut shapes = Vec::new();
+        visit(root, &Style::default(), IDENTITY, &mut shapes)?;
+        Ok(Self { view_box, shapes })
+    }
+
+    /// Adds the document to the canvas scaled to the height and centered on (x, y).
+    ///
+    /// SVG colors are in sRGB and are converted to the linear colors used by the canvas.
+    pub fn draw(
+        &self,
+        canvas: &mut Canvas,
+        x: f32,
+        y: f32,
+        height: f32,
+    ) -> Result<Vec<SvgElement>, Error> {
+        let [vx, vy, vw, vh] = self.view_box;
+        let scale = if vh > 0.0 { height / vh } else { 1.0 };
+        // SVG's y axis points down, the canvas' points up
+        let placement = [
+            scale,
+            0.0,
+            0.0,
+            -scale,
+            x - (vx + vw * 0.5) * scale,
+            y + (vy + vh * 0.5) * scale,
+        ];
+        let tolerance = canvas.tolerance();
+
+        let mut elements = Vec::with_capacity(self.shapes.len());
+        for shape in &self.shapes {
+            let transform = multiply(placement, shape.transform);
+            let scale = transform_scale(transform);
+            let polylines = shape
+                .path
+                .flatten(tolerance / scale.max(f32::EPSILON))
+                .into_iter()
+                .map(|polyline| Polyline {
+                    points: polyline
+                        .points
+                        .iter()
+                        .map(|point| apply(transform, *point))
+                        .collect(),
+                    closed: polyline.closed,
+                })
+                .collect::<Vec<_>>();
+
+            let fill = match shape.fill {
+                Some((color, rule)) => {
+                    let mesh = canvas.mesh(path::fill(&polylines, rule))?;
+                    canvas.color(&mesh, color)?;
+                    Some(mesh)
+                }
+                None => None,
+            };
+            let stroke = match shape.stroke {
+                Some((color, style)) => {
+                    let style = StrokeStyle {
+                        width: style.width * scale,
+                        ..style
+                    };
+                    let mesh = canvas.mesh(path::stroke(&polylines, &style, tolerance))?;
+                    canvas.color(&mesh, color)?;
+                    Some(mesh)
+                }
+                None => None,
+            };
+            elements.push(SvgElement {
+                id: shape.id.clone(),
+                fill,
+                stroke,
+            });
+        }
+        Ok(elements)
+    }
+}
+
+fn visit(
+    node: roxmltree::Node,
+    parent: &Style,
+    parent_transform: Transform2d,
+    shapes: &mut Vec<Shape>,
+) -> Result<(), Error> {
+    let style = style(node, parent)?;
+    let transform = match node.attribute("transform") {
+        Some(transform) => multiply(parent_transform, parse_transform(transform)?),
+        None => parent_transform,
+    };
+    let name = node.tag_name().name();
+    match name {
+        "svg" | "g" => {
+            for child in node.children().filter(|child| child.is_element()) {
+                visit(child, &style, transform, shapes)?;
+            }
+        }
+        "path" | "rect" | "circle" | "ellipse" | "line" | "polyline" | "polygon" => {
+            let path = geometry(node)?;
+            if path.is_empty() {
+                return Ok(());
+            }
+            // Only polyline and line are left open, every other shape encloses an area
+            let fill = style.fill.filter(|_| name != "line").map(|[r, g, b, a]| {
+                (
+                    [r, g, b, a * style.fill_opacity * style.opacity],
+                    style.fill_rule,
+                )
+            });
+            let stroke = style.stroke.map(|[r, g, b, a]| {
+                (
+                    [r, g, b, a * style.stroke_opacity * style.opacity],
+                    StrokeStyle::new(style.stroke_width)
+                        .with_join(style.stroke_join)
+                        .with_cap(style.stroke_cap)
+                        .with_miter_limit(style.miter_limit),
+                )
+            });
+            if fill.is_some() || stroke.is_some() {
+                shapes.push(Shape {
+                    id: node.attribute("id").map(String::from),
+                    path,
+                    transform,
+                    fill,
+                    stroke,
+                });
+            }
+        }
+        _ => log::debug!("Skipping unsupported SVG element <{}>", name),
+    }
+    Ok(())
+}
+
+/// Builds the path of a shape element.
+fn geometry(node: roxmltree::Node) -> Result<Path, Error> {
+    let attribute =
+        |name: &str| -> Result<f32, Error> { Ok(length(node.attribute(name))?.unwrap_or(0.0)) };
+    let path = match node.tag_name().name() {
+        "path" => parse_path(node.attribute("d").unwrap_or(""))?,
+        "rect" => {
+            let (x, y, w, h) = (
+                attribute("x")?,
+                attribute("y")?,
+                attribute("width")?,
+                attribute("height")?,
+            );
+            if w <= 0.0 || h <= 0.0 {
+                return Ok(Path::new());
+            }
+            // A missing corner radius defaults to the other one
+            let rx = length(node.attribute("rx"))?;
+            let ry = length(node.attribute("ry"))?;
+            let rx = rx.or(ry).unwrap_or(0.0).min(w * 0.5);
+            let ry = ry.or(Some(rx)).unwrap_or(0.0).min(h * 0.5);
+            if rx > 0.0 && ry > 0.0 {
+                Path::new()
+                    .move_to([x + rx, y])
+                    .line_to([x + w - rx, y])
+                    .arc_to([rx, ry], 0.0, false, true, [x + w, y + ry])
+                    .line_to([x + w, y + h - ry])
+                    .arc_to([rx, ry], 0.0, false, true, [x + w - rx, y + h])
+                    .line_to([x + rx, y + h])
+                    .arc_to([rx, ry], 0.0, false, true, [x, y + h - ry])
+                    .line_to([x, y + ry])
+                    .arc_to([rx, ry], 0.0, false, true, [x + rx, y])
+                    .close()
+            } else {
+                Path::new()
+                    .move_to([x, y])
+                    .line_to([x + w, y])
+                    .line_to([x + w, y + h])
+                    .line_to([x, y + h])
+                    .close()
+            }
+        }
+        "circle" => {
+            let r = attribute("r")?;
+            ellipse(attribute("cx")?, attribute("cy")?, r, r)
+        }
+        "ellipse" => ellipse(
+            attribute("cx")?,
+            attribute("cy")?,
+            attribute("rx")?,
+            attribute("ry")?,
+        ),
+        "line" => Path::new()
+            .move_to([attribute("x1")?, attribute("y1")?])
+            .line_to([attribute("x2")?, attribute("y2")?]),
+        name => {
+            let numbers = numbers(node.attribute("points").unwrap_or(""))?;
+            let mut points = numbers.chunks_exact(2).map(|p| [p[0], p[1]]);
+            let mut path = match points.next() {
+                Some(first) => Path::new().move_to(first),
+                None => return Ok(Path::new()),
+            };
+            for point in points {
+                path = path.line_to(point);
+            }
+            if name == "polygon" {
+                path = path.close();
+            }
+            path
+        }
+    };
+    Ok(path)
+}
+
+fn ellipse(cx: f32, cy: f32, rx: f32, ry: f32) -> Path {
+    if rx <= 0.0 || ry <= 0.0 {
+        return Path::new();
+    }
+    Path::new()
+        .move_to([cx + rx, cy])
+        .arc_to([rx, ry], 0.0, false, true, [cx - rx, cy])
+        .arc_to([rx, ry], 0.0, false, true, [cx + rx, cy])
+        .close()
+}
+
+/// Resolves the style of the element from its attributes and inline style on top of its parent's.
+fn style(node: roxmltree::Node, parent: &Style) -> Result<Style, Error> {
+    let mut style = parent.clone();
+    let inline = node
+        .attribute("style")
+        .unwrap_or("")
+        .split(';')
+        .filter_map(|declaration| {
+            let mut parts = declaration.splitn(2, ':');
+            Some((parts.next()?.trim(), parts.next()?.trim()))
+        });
+    let attributes = [
+        "fill",
+        "fill-rule",
+        "fill-opacity",
+        "stroke",
+        "stroke-opacity",
+        "stroke-width",
+        "stroke-linejoin",
+        "stroke-linecap",
+        "stroke-miterlimit",
+        "opacity",
+    ]
+    .iter()
+    .filter_map(|name| node.attribute(*name).map(|value| (*name, value.trim())));
+    // Inline style takes precedence over presentation attributes
+    for (name, value) in attributes.chain(inline) {
+        if value == "inherit" {
+            continue;
+        }
+        match name {
+            "fill" => style.fill = paint(value),
+            "fill-rule" => {
+                style.fill_rule = match value {
+                    "evenodd" => FillRule::EvenOdd,
+                    _ => FillRule::NonZero,
+                }
+            }
+            "fill-opacity" => style.fill_opacity = opacity(value).unwrap_or(style.fill_opacity),
+            "stroke" => style.stroke = paint(value),
+            "stroke-opacity" => {
+                style.stroke_opacity = opacity(value).unwrap_or(style.stroke_opacity)
+            }
+            "stroke-width" => style.stroke_width = length(Some(value))?.unwrap_or(1.0),
+            "stroke-linejoin" => {
+                style.stroke_join = match value {
+                    "round" => LineJoin::Round,
+                    "bevel" => LineJoin::Bevel,
+                    _ => LineJoin::Miter,
+                }
+            }
+            "stroke-linecap" => {
+                style.stroke_cap = match value {
+                    "round" => LineCap::Round,
+                    "square" => LineCap::Square,
+                    _ => LineCap::Butt,
+                }
+            }
+            "stroke-miterlimit" => style.miter_limit = number(value)?,
+            // Group opacity is approximated by multiplying it into the children's colors
+            "opacity" => {
+                if let Some(opacity) = opacity(value) {
+                    style.opacity = parent.opacity * opacity;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(style)
+}
+
+/// Parses a paint value, only solid colors are supported, anything else is not painted.
+///
+/// Colors that can't be parsed are skipped with a warning rather than failing the import.
+fn paint(value: &str) -> Option<Rgba> {
+    if value == "none" || value == "transparent" || value.starts_with("url(") {
+        return None;
+    }
+    match color(value) {
+        Some([r, g, b, a]) => Some([linear(r), linear(g), linear(b), a]),
+        None => {
+            log::warn!("Skipping unsupported SVG color '{}'", value);
+            None
+        }
+    }
+}
+
+/// Parses an opacity given as a number or a percentage, clamped between 0 and 1.
+///
+/// Like colors, values that can't be parsed are skipped with a warning.
+fn opacity(value: &str) -> Option<f32> {
+    match fraction(value, 1.0).filter(|opacity| !opacity.is_nan()) {
+        Some(opacity) => Some(opacity.clamp(0.0, 1.0)),
+        None => {
+            log::warn!("Skipping unsupported SVG opacity '{}'", value);
+            None
+        }
+    }
+}
+
+/// Parses a hex, `rgb()`, `rgba()`, `hsl()`, `hsla()` or named color into sRGB and alpha.
+fn color(value: &str) -> Option<[f32; 4]> {
+    if let Some(hex) = value.strip_prefix('#') {
+        let digits = hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| d as f32))
+            .collect::<Option<Vec<_>>>()?;
+        return match digits.len() {
+            // Short forms repeat each digit, so 0xf is 0xff
+            3 | 4 => {
+                let alpha = digits.get(3).map_or(1.0, |a| a / 15.0);
+                Some([digits[0] / 15.0, digits[1] / 15.0, digits[2] / 15.0, alpha])
+            }
+            6 | 8 => {
+                let channel = |i: usize| (digits[i] * 16.0 + digits[i + 1]) / 255.0;
+                let alpha = if digits.len() == 8 { channel(6) } else { 1.0 };
+                Some([channel(0), channel(2), channel(4), alpha])
+            }
+            _ => None,
+        };
+    }
+    if let Some((function, arguments)) = function(value) {
+        // Arguments are separated by commas or spaces, the alpha may follow a slash
+        let arguments = arguments
+            .split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+            .filter(|argument| !argument.is_empty())
+            .collect::<Vec<_>>();
+        if arguments.len() != 3 && arguments.len() != 4 {
+            return None;
+        }
+        let alpha = match arguments.get(3) {
+            Some(alpha) => fraction(alpha, 1.0)?,
+            None => 1.0,
+        };
+        let [r, g, b] = match function {
+            "rgb" | "rgba" => [
+                fraction(arguments[0], 255.0)?,
+                fraction(arguments[1], 255.0)?,
+                fraction(arguments[2], 255.0)?,
+            ],
+            "hsl" | "hsla" => {
+                let hue = number(arguments[0].trim_end_matches("deg")).ok()?;
+                let saturation = number(arguments[1].strip_suffix('%')?).ok()? / 100.0;
+                let lightness = number(arguments[2].strip_suffix('%')?).ok()? / 100.0;
+                hsl_to_rgb(hue, saturation, lightness)
+            }
+            _ => return None,
+        };
+        return Some([r, g, b, alpha]);
+    }
+    let name = value.to_ascii_lowercase();
+    if name == "currentcolor" {
+        return Some([0.0, 0.0, 0.0, 1.0]);
+    }
+    NAMED_COLORS
+        .binary_search_by_key(&name.as_str(), |(name, _)| name)
+        .ok()
+        .map(|i| {
+            let [r, g, b] = NAMED_COLORS[i].1;
+            [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0]
+        })
+}
+
+/// Splits `name(arguments)` into the name and the arguments.
+fn function(value: &str) -> Option<(&str, &str)> {
+    let open = value.find('(')?;
+    let arguments = value[open + 1..].strip_suffix(')')?;
+    Some((value[..open].trim(), arguments))
+}
+
+/// Parses a number scaled down by `max`, or a percentage.
+fn fraction(value: &str, max: f32) -> Option<f32> {
+    match value.strip_suffix('%') {
+        Some(percent) => number(percent).ok().map(|p| p / 100.0),
+        None => number(value).ok().map(|v| v / max),
+    }
+}
+
+/// Converts a hue in degrees with saturation and lightness between 0 and 1 to sRGB.
+fn hsl_to_rgb(hue: f32, saturation: f32, lightness: f32) -> [f32; 3] {
+    let (s, l) = (saturation.clamp(0.0, 1.0), lightness.clamp(0.0, 1.0));
+    let q = if l < 0.5 {
+        l * (1.0 + s)
+    } else {
+        l + s - l * s
+    };
+    let p = 2.0 * l - q;
+    let channel = |t: f32| {
+        let t = t.rem_euclid(1.0);
+        if t < 1.0 / 6.0 {
+            p + (q - p) * 6.0 * t
+        } else if t < 0.5 {
+            q
+        } else if t < 2.0 / 3.0 {
+            p + (q - p) * (2.0 / 3.0 - t) * 6.0
+        } else {
+            p
+        }
+    };
+    let h = hue.rem_euclid(360.0) / 360.0;
+    [channel(h + 1.0 / 3.0), channel(h), channel(h - 1.0 / 3.0)]
+}
+
+/// Converts an sRGB encoded channel to linear.
+fn linear(c: f32) -> f32 {
+    let c = c.clamp(0.0, 1.0);
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Parses the value of a `transform` attribute.
+fn parse_transform(value: &str) -> Result<Transform2d, Error> {
+    let mut transform = IDENTITY;
+    let mut rest = value.trim();
+    while !rest.is_empty() {
+        let open = rest
+            .find('(')
+            .ok_or_else(|| Error::InvalidSvg(format!("transform '{}'", value)))?;
+        let close = rest
+            .find(')')
+            .ok_or_else(|| Error::InvalidSvg(format!("transform '{}'", value)))?;
+        let name = rest[..open].trim_matches(|c: char| c.is_whitespace() || c == ',');
+        let args = numbers(&rest[open + 1..close])?;
+        let arg = |i: usize| args.get(i).copied().unwrap_or(0.0);
+        let next = match (name, args.len()) {
+            ("matrix", 6) => [arg(0), arg(1), arg(2), arg(3), arg(4), arg(5)],
+            ("translate", 1) | ("translate", 2) => [1.0, 0.0, 0.0, 1.0, arg(0), arg(1)],
+            ("scale", 1) => [arg(0), 0.0, 0.0, arg(0), 0.0, 0.0],
+            ("scale", 2) => [arg(0), 0.0, 0.0, arg(1), 0.0, 0.0],
+            ("rotate", 1) | ("rotate", 3) => {
+                let (sin, cos) = arg(0).to_radians().sin_cos();
+                let (cx, cy) = (arg(1), arg(2));
+                let rotation = [cos, sin, -sin, cos, 0.0, 0.0];
+                multiply(
+                    multiply([1.0, 0.0, 0.0, 1.0, cx, cy], rotation),
+                    [1.0, 0.0, 0.0, 1.0, -cx, -cy],
+                )
+            }
+            ("skewX", 1) => [1.0, 0.0, arg(0).to_radians().tan(), 1.0, 0.0, 0.0],
+            ("skewY", 1) => [1.0, arg(0).to_radians().tan(), 0.0, 1.0, 0.0, 0.0],
+            _ => return Err(Error::InvalidSvg(format!("transform '{}'", value))),
+        };
+        transform = multiply(transform, next);
+        rest = rest[close + 1..].trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+    }
+    Ok(transform)
+}
+
+/// Returns the transform that applies `b` then `a`.
+fn multiply(a: Transform2d, b: Transform2d) -> Transform2d {
+    [
+        a[0] * b[0] + a[2] * b[1],
+        a[1] * b[0] + a[3] * b[1],
+        a[0] * b[2] + a[2] * b[3],
+        a[1] * b[2] + a[3] * b[3],
+        a[0] * b[4] + a[2] * b[5] + a[4],
+        a[1] * b[4] + a[3] * b[5] + a[5],
+    ]
+}
+
+fn apply(t: Transform2d, [x, y]: Vec2) -> Vec2 {
+    [t[0] * x + t[2] * y + t[4], t[1] * x + t[3] * y + t[5]]
+}
+
+/// Average scale factor of the transform, used to scale tolerances and stroke widths.
+fn transform_scale(t: Transform2d) -> f32 {
+    (t[0] * t[3] - t[1] * t[2]).abs().sqrt()
+}
+
+/// Parses SVG path data into a path, relative commands are resolved to absolute points.
+fn parse_path(data: &str) -> Result<Path, Error> {
+    let invalid = || Error::InvalidSvg(format!("path data '{}'", data));
+    let mut path = Path::new();
+    let mut tokens = PathTokens {
+        data: data.as_bytes(),
+        position: 0,
+    };
+    let mut command = None;
+    let mut current = [0.0, 0.0];
+    let mut start = [0.0, 0.0];
+    // Control point of the previous curve, reflected by the smooth curve commands
+    let mut last_cubic_ctrl: Option<Vec2> = None;
+    let mut last_quad_ctrl: Option<Vec2> = None;
+
+    while let Some(token) = tokens.next_command() {
+        let letter = match token {
+            Some(letter) => {
+                command = Some(letter);
+                letter
+            }
+            // A number without a command repeats the previous command
+            None => match command {
+                Some(b'M') => b'L',
+                Some(b'm') => b'l',
+                Some(b'Z') | Some(b'z') | None => return Err(invalid()),
+                Some(letter) => letter,
+            },
+        };
+        if token.is_none() {
+            command = Some(letter);
+        }
+        let relative = letter.is_ascii_lowercase();
+        let origin = if relative { current } else { [0.0, 0.0] };
+        let point = |tokens: &mut PathTokens| -> Result<Vec2, Error> {
+            let x = tokens.number().ok_or_else(invalid)?;
+            let y = tokens.number().ok_or_else(invalid)?;
+            Ok([origin[0] + x, origin[1] + y])
+        };
+        let (mut cubic_ctrl, mut quad_ctrl) = (None, None);
+        match letter.to_ascii_uppercase() {
+            b'M' => {
+                current = point(&mut tokens)?;
+                start = current;
+                path = path.move_to(current);
+            }
+            b'L' => {
+                current = point(&mut tokens)?;
+                path = path.line_to(current);
+            }
+            b'H' => {
+                let x = tokens.number().ok_or_else(invalid)?;
+                current = [origin[0] + x, current[1]];
+                path = path.line_to(current);
+            }
+            b'V' => {
+                let y = tokens.number().ok_or_else(invalid)?;
+                current = [current[0], origin[1] + y];
+                path = path.line_to(current);
+            }
+            b'C' => {
+                let ctrl1 = point(&mut tokens)?;
+                let ctrl2 = point(&mut tokens)?;
+                current = point(&mut tokens)?;
+                path = path.cubic_to(ctrl1, ctrl2, current);
+                cubic_ctrl = Some(ctrl2);
+            }
+            b'S' => {
+                let ctrl1 = reflect(last_cubic_ctrl, current);
+                let ctrl2 = point(&mut tokens)?;
+                current = point(&mut tokens)?;
+                path = path.cubic_to(ctrl1, ctrl2, current);
+                cubic_ctrl = Some(ctrl2);
+            }
+            b'Q' => {
+                let ctrl = point(&mut tokens)?;
+                current = point(&mut tokens)?;
+                path = path.quad_to(ctrl, current);
+                quad_ctrl = Some(ctrl);
+            }
+            b'T' => {
+                let ctrl = reflect(last_quad_ctrl, current);
+                current = point(&mut tokens)?;
+                path = path.quad_to(ctrl, current);
+                quad_ctrl = Some(ctrl);
+            }
+            b'A' => {
+                let rx = tokens.number().ok_or_else(invalid)?;
+                let ry = tokens.number().ok_or_else(invalid)?;
+                let x_rotation = tokens.number().ok_or_else(invalid)?;
+                let large_arc = tokens.flag().ok_or_else(invalid)?;
+                let sweep = tokens.flag().ok_or_else(invalid)?;
+                current = point(&mut tokens)?;
+                path = path.arc_to([rx, ry], x_rotation.to_radians(), large_arc, sweep, current);
+            }
+            b'Z' => {
+                current = start;
+                path = path.close();
+            }
+            _ => return Err(invalid()),
+        }
+        last_cubic_ctrl = cubic_ctrl;
+        last_quad_ctrl = quad_ctrl;
+    }
+    if !tokens.at_end() {
+        return Err(invalid());
+    }
+    Ok(path)
+}
+
+fn reflect(ctrl: Option<Vec2>, about: Vec2) -> Vec2 {
+    match ctrl {
+        Some([x, y]) => [2.0 * about[0] - x, 2.0 * about[1] - y],
+        None => about,
+    }
+}
+
+struct PathTokens<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> PathTokens<'a> {
+    fn skip_separators(&mut self) {
+        while self.position < self.data.len()
+            && (self.data[self.position].is_ascii_whitespace() || self.data[self.position] == b',')
+        {
+            self.position += 1;
+        }
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_separators();
+        self.position >= self.data.len()
+    }
+
+    /// Returns `Some(Some(letter))` for a command letter, `Some(None)` if a number follows
+    /// instead and `None` at the end of the data.
+    fn next_command(&mut self) -> Option<Option<u8>> {
+        self.skip_separators();
+        let byte = *self.data.get(self.position)?;
+        if byte.is_ascii_alphabetic() && byte != b'e' && byte != b'E' {
+            self.position += 1;
+            Some(Some(byte))
+        } else {
+            Some(None)
+        }
+    }
+
+    fn number(&mut self) -> Option<f32> {
+        self.skip_separators();
+        let start = self.position;
+        let mut end = start;
+        let bytes = self.data;
+        if end < bytes.len() && (bytes[end] == b'-' || bytes[end] == b'+') {
+            end += 1;
+        }
+        let mut seen_dot = false;
+        while end < bytes.len()
+            && (bytes[end].is_ascii_digit() || (bytes[end] == b'.' && !seen_dot))
+        {
+            seen_dot |= bytes[end] == b'.';
+            end += 1;
+        }
+        if end < bytes.len() && (bytes[end] == b'e' || bytes[end] == b'E') {
+            let mut exponent = end + 1;
+            if exponent < bytes.len() && (bytes[exponent] == b'-' || bytes[exponent] == b'+') {
+                exponent += 1;
+            }
+            if exponent < bytes.len() && bytes[exponent].is_ascii_digit() {
+                end = exponent;
+                while end < bytes.len() && bytes[end].is_ascii_digit() {
+                    end += 1;
+                }
+            }
+        }
+        let number = std::str::from_utf8(&bytes[start..end]).ok()?.parse().ok()?;
+        self.position = end;
+        Some(number)
+    }
+
+    /// Arc flags are a single digit and may be written without separators e.g. `a1 1 0 00 1 1`.
+    fn flag(&mut self) -> Option<bool> {
+        self.skip_separators();
+        let flag = match self.data.get(self.position)? {
+            b'0' => false,
+            b'1' => true,
+            _ => return None,
+        };
+        self.position += 1;
+        Some(flag)
+    }
+}
+
+fn number(value: &str) -> Result<f32, Error> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| Error::InvalidSvg(format!("number '{}'", value)))
+}
+
+/// Parses a length in user units, `px` is accepted and percentages are not supported.
+fn length(value: Option<&str>) -> Result<Option<f32>, Error> {
+    match value {
+        Some(value) if !value.trim().ends_with('%') => {
+            number(value.trim().trim_end_matches("px")).map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Parses a list of numbers separated by whitespace and/or commas.
+fn numbers(value: &str) -> Result<Vec<f32>, Error> {
+    let mut tokens = PathTokens {
+        data: value.as_bytes(),
+        position: 0,
+    };
+    let mut numbers = Vec::new();
+    while !tokens.at_end() {
+        numbers.push(
+            tokens
+                .number()
+                .ok_or_else(|| Error::InvalidSvg(format!("numbers '{}'", value)))?,
+        );
+    }
+    Ok(numbers)
+}
+
+/// The SVG and CSS color keywords, sorted by name for binary search.
+const NAMED_COLORS: &[(&str, [u8; 3])] = &[
+    ("aliceblue", [240, 248, 255]),
+    ("antiquewhite", [250, 235, 215]),
+    ("aqua", [0, 255, 255]),
+    ("aquamarine", [127, 255, 212]),
+    ("azure", [240, 255, 255]),
+    ("beige", [245, 245, 220]),
+    ("bisque", [255, 228, 196]),
+    ("black", [0, 0, 0]),
+    ("blanchedalmond", [255, 235, 205]),
+    ("blue", [0, 0, 255]),
+    ("blueviolet", [138, 43, 226]),
+    ("brown", [165, 42, 42]),
+    ("burlywood", [222, 184, 135]),
+    ("cadetblue", [95, 158, 160]),
+    ("chartreuse", [127, 255, 0]),
+    ("chocolate", [210, 105, 30]),
+    ("coral", [255, 127, 80]),
+    ("cornflowerblue", [100, 149, 237]),
+    ("cornsilk", [255, 248, 220]),
+    ("crimson", [220, 20, 60]),
+    ("cyan", [0, 255, 255]),
+    ("darkblue", [0, 0, 139]),
+    ("darkcyan", [0, 139, 139]),
+    ("darkgoldenrod", [184, 134, 11]),
+    ("darkgray", [169, 169, 169]),
+    ("darkgreen", [0, 100, 0]),
+    ("darkgrey", [169, 169, 169]),
+    ("darkkhaki", [189, 183, 107]),
+    ("darkmagenta", [139, 0, 139]),
+    ("darkolivegreen", [85, 107, 47]),
+    ("darkorange", [255, 140, 0]),
+    ("darkorchid", [153, 50, 204]),
+    ("darkred", [139, 0, 0]),
+    ("darksalmon", [233, 150, 122]),
+    ("darkseagreen", [143, 188, 143]),
+    ("darkslateblue", [72, 61, 139]),
+    ("darkslategray", [47, 79, 79]),
+    ("darkslategrey", [47, 79, 79]),
+    ("darkturquoise", [0, 206, 209]),
+    ("darkviolet", [148, 0, 211]),
+    ("deeppink", [255, 20, 147]),
+    ("deepskyblue", [0, 191, 255]),
+    ("dimgray", [105, 105, 105]),
+    ("dimgrey", [105, 105, 105]),
+    ("dodgerblue", [30, 144, 255]),
+    ("firebrick", [178, 34, 34]),
+    ("floralwhite", [255, 250, 240]),
+    ("forestgreen", [34, 139, 34]),
+    ("fuchsia", [255, 0, 255]),
+    ("gainsboro", [220, 220, 220]),
+    ("ghostwhite", [248, 248, 255]),
+    ("gold", [255, 215, 0]),
+    ("goldenrod", [218, 165, 32]),
+    ("gray", [128, 128, 128]),
+    ("green", [0, 128, 0]),
+    ("greenyellow", [173, 255, 47]),
+    ("grey", [128, 128, 128]),
+    ("honeydew", [240, 255, 240]),
+    ("hotpink", [255, 105, 180]),
+    ("indianred", [205, 92, 92]),
+    ("indigo", [75, 0, 130]),
+    ("ivory", [255, 255, 240]),
+    ("khaki", [240, 230, 140]),
+    ("lavender", [230, 230, 250]),
+    ("lavenderblush", [255, 240, 245]),
+    ("lawngreen", [124, 252, 0]),
+    ("lemonchiffon", [255, 250, 205]),
+    ("lightblue", [173, 216, 230]),
+    ("lightcoral", [240, 128, 128]),
+    ("lightcyan", [224, 255, 255]),
+    ("lightgoldenrodyellow", [250, 250, 210]),
+    ("lightgray", [211, 211, 211]),
+    ("lightgreen", [144, 238, 144]),
+    ("lightgrey", [211, 211, 211]),
+    ("lightpink", [255, 182, 193]),
+    ("lightsalmon", [255, 160, 122]),
+    ("lightseagreen", [32, 178, 170]),
+    ("lightskyblue", [135, 206, 250]),
+    ("lightslategray", [119, 136, 153]),
+    ("lightslategrey", [119, 136, 153]),
+    ("lightsteelblue", [176, 196, 222]),
+    ("lightyellow", [255, 255, 224]),
+    ("lime", [0, 255, 0]),
+    ("limegreen", [50, 205, 50]),
+    ("linen", [250, 240, 230]),
+    ("magenta", [255, 0, 255]),
+    ("maroon", [128, 0, 0]),
+    ("mediumaquamarine", [102, 205, 170]),
+    ("mediumblue", [0, 0, 205]),
+    ("mediumorchid", [186, 85, 211]),
+    ("mediumpurple", [147, 112, 219]),
+    ("mediumseagreen", [60, 179, 113]),
+    ("mediumslateblue", [123, 104, 238]),
+    ("mediumspringgreen", [0, 250, 154]),
+    ("mediumturquoise", [72, 209, 204]),
+    ("mediumvioletred", [199, 21, 133]),
+    ("midnightblue", [25, 25, 112]),
+    ("mintcream", [245, 255, 250]),
+    ("mistyrose", [255, 228, 225]),
+    ("moccasin", [255, 228, 181]),
+    ("navajowhite", [255, 222, 173]),
+    ("navy", [0, 0, 128]),
+    ("oldlace", [253, 245, 230]),
+    ("olive", [128, 128, 0]),
+    ("olivedrab", [107, 142, 35]),
+    ("orange", [255, 165, 0]),
+    ("orangered", [255, 69, 0]),
+    ("orchid", [218, 112, 214]),
+    ("palegoldenrod", [238, 232, 170]),
+    ("palegreen", [152, 251, 152]),
+    ("paleturquoise", [175, 238, 238]),
+    ("palevioletred", [219, 112, 147]),
+    ("papayawhip", [255, 239, 213]),
+    ("peachpuff", [255, 218, 185]),
+    ("peru", [205, 133, 63]),
+    ("pink", [255, 192, 203]),
+    ("plum", [221, 160, 221]),
+    ("powderblue", [176, 224, 230]),
+    ("purple", [128, 0, 128]),
+    ("rebeccapurple", [102, 51, 153]),
+    ("red", [255, 0, 0]),
+    ("rosybrown", [188, 143, 143]),
+    ("royalblue", [65, 105, 225]),
+    ("saddlebrown", [139, 69, 19]),
+    ("salmon", [250, 128, 114]),
+    ("sandybrown", [244, 164, 96]),
+    ("seagreen", [46, 139, 87]),
+    ("seashell", [255, 245, 238]),
+    ("sienna", [160, 82, 45]),
+    ("silver", [192, 192, 192]),
+    ("skyblue", [135, 206, 235]),
+    ("slateblue", [106, 90, 205]),
+    ("slategray", [112, 128, 144]),
+    ("slategrey", [112, 128, 144]),
+    ("snow", [255, 250, 250]),
+    ("springgreen", [0, 255, 127]),
+    ("steelblue", [70, 130, 180]),
+    ("tan", [210, 180, 140]),
+    ("teal", [0, 128, 128]),
+    ("thistle", [216, 191, 216]),
+    ("tomato", [255, 99, 71]),
+    ("turquoise", [64, 224, 208]),
+    ("violet", [238, 130, 238]),
+    ("wheat", [245, 222, 179]),
+    ("white", [255, 255, 255]),
+    ("whitesmoke", [245, 245, 245]),
+    ("yellow", [255, 255, 0]),
+    ("yellowgreen", [154, 205, 50]),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn named_colors_are_sorted() {
+        assert!(NAMED_COLORS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    fn assert_color(value: &str, expected: [f32; 4]) {
+        let actual = color(value).unwrap_or_else(|| panic!("'{}' is not a color", value));
+        assert!(
+            actual
+                .iter()
+                .zip(&expected)
+                .all(|(a, e)| (a - e).abs() < 1e-5),
+            "'{}' is {:?}, expected {:?}",
+            value,
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn parses_colors() {
+        let red = [1.0, 0.0, 0.0, 1.0];
+        assert_color("red", red);
+        assert_color("Red", red);
+        assert_color("#f00", red);
+        assert_color("#ff0000", red);
+        assert_color("rgb(255, 0, 0)", red);
+        assert_color("rgb(100%,0%,0%)", red);
+        assert_color("rgb(255 0 0)", red);
+        assert_color("hsl(0, 100%, 50%)", red);
+        assert_color("hsl(360deg 100% 50%)", red);
+        assert_color(
+            "darkgray",
+            [169.0 / 255.0, 169.0 / 255.0, 169.0 / 255.0, 1.0],
+        );
+        assert_color("#0000ff80", [0.0, 0.0, 1.0, 128.0 / 255.0]);
+        assert_color("#00f8", [0.0, 0.0, 1.0, 8.0 / 15.0]);
+        assert_color("rgba(0, 0, 255, 0.5)", [0.0, 0.0, 1.0, 0.5]);
+        assert_color("rgb(0 0 255 / 50%)", [0.0, 0.0, 1.0, 0.5]);
+        assert_color("hsla(120, 100%, 25%, 0.25)", [0.0, 0.5, 0.0, 0.25]);
+    }
+
+    #[test]
+    fn skips_unsupported_paint() {
+        assert_eq!(paint("none"), None);
+        assert_eq!(paint("url(#gradient)"), None);
+        assert_eq!(paint("notacolor"), None);
+        assert_eq!(paint("#12345"), None);
+        assert_eq!(paint("rgb(1, 2)"), None);
+        assert_eq!(paint("hsl(0, 1, 1)"), None);
+        assert_eq!(paint("white"), Some([1.0, 1.0, 1.0, 1.0]));
+    }
+
+    #[test]
+    fn import_keeps_going_past_unsupported_colors() {
+        let svg = Svg::parse(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+                <rect width="4" height="4" fill="color(display-p3 1 0 0)" stroke="darkgray"/>
+                <circle cx="5" cy="5" r="2" fill="hsla(200, 50%, 50%, 0.5)"/>
+            </svg>"##,
+        )
+        .unwrap();
+        assert_eq!(svg.shapes.len(), 2);
+        assert!(svg.shapes[0].fill.is_none());
+        assert!(svg.shapes[0].stroke.is_some());
+        assert_eq!(svg.shapes[1].fill.unwrap().0[3], 0.5);
+    }
+
+    #[test]
+    fn parses_opacity() {
+        assert_eq!(opacity("0.25"), Some(0.25));
+        assert_eq!(opacity("50%"), Some(0.5));
+        assert_eq!(opacity("1.5"), Some(1.0));
+        assert_eq!(opacity("-20%"), Some(0.0));
+        assert_eq!(opacity("half"), None);
+        assert_eq!(opacity("NaN"), None);
+    }
+
+    #[test]
+    fn import_keeps_going_past_unsupported_opacity() {
+        let svg = Svg::parse(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+                <g opacity="50%">
+                    <rect width="4" height="4" fill="white" fill-opacity="lots"/>
+                    <circle cx="5" cy="5" r="2" fill="white" style="fill-opacity: 2"/>
+                </g>
+            </svg>"##,
+        )
+        .unwrap();
+        assert_eq!(svg.shapes.len(), 2);
+        assert_eq!(svg.shapes[0].fill.unwrap().0[3], 0.5);
+        assert_eq!(svg.shapes[1].fill.unwrap().0[3], 0.5);
+    }
+
+    #[test]
+    fn parses_path_data() {
+        let path = parse_path("M0 0 L10 0 l0 10 H0 z").unwrap();
+        assert_eq!(
+            path.flatten(0.1)[0].points,
+            vec![[0.0, 0.0], [10.0, 0.0], [10.0, 10.0], [0.0, 10.0]]
+        );
+        // Numbers may run together when the sign or a second decimal point starts the next one
+        let compact = parse_path("M1-2.5.5.5L3,4").unwrap();
+        assert_eq!(
+            compact.flatten(0.1)[0].points,
+            vec![[1.0, -2.5], [0.5, 0.5], [3.0, 4.0]]
+        );
+        assert!(parse_path("M0 0 L10").is_err());
+        assert!(parse_path("X0 0").is_err());
+    }
+
+    #[test]
+    fn parses_transforms() {
+        let t = parse_transform("translate(10, 20) scale(2)").unwrap();
+        assert_eq!(apply(t, [1.0, 1.0]), [12.0, 22.0]);
+        let rotated = apply(parse_transform("rotate(90)").unwrap(), [1.0, 0.0]);
+        assert!(rotated[0].abs() < 1e-6 && (rotated[1] - 1.0).abs() < 1e-6);
+        assert!(parse_transform("skew(10)").is_err());
+    }
+}
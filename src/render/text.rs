@@ -0,0 +1,612 @@
+//! Text rendering with glyphs rasterized into a texture atlas.
+use super::atlas::ShelfPacker;
+use super::error::Error;
+use super::math::*;
+use super::mesh::Mesh;
+use super::texture::{self, TexelRect};
+pub(crate) use ab_glyph::GlyphId;
+use ab_glyph::{Font as _, ScaleFont as _};
+use std::collections::HashMap;
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Size of the glyph atlas texture in pixels.
+const ATLAS_SIZE: u32 = 2048;
+/// Default size glyphs are rasterized at, in pixels per em.
+const DEFAULT_PIXEL_SIZE: f32 = 48.0;
+
+static NEXT_FONT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A TrueType or OpenType font.
+#[derive(Clone, Debug)]
+pub struct Font {
+    /// Identifies the font's glyphs in the glyph atlas.
+    id: usize,
+    font: ab_glyph::FontArc,
+    pixel_size: f32,
+}
+
+impl Font {
+    /// Loads a TTF or OTF font from its file contents.
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, Error> {
+        let font = ab_glyph::FontArc::try_from_vec(bytes)?;
+        Ok(Self {
+            id: NEXT_FONT_ID.fetch_add(1, Ordering::Relaxed),
+            font,
+            pixel_size: DEFAULT_PIXEL_SIZE,
+        })
+    }
+
+    /// Sets the size glyphs are rasterized at in pixels per em.
+    ///
+    /// Text looks sharpest when this matches the size the text has on screen.
+    pub fn with_pixel_size(mut self, pixel_size: f32) -> Self {
+        self.pixel_size = pixel_size;
+        self
+    }
+
+    pub(crate) fn font(&self) -> &ab_glyph::FontArc {
+        &self.font
+    }
+
+    pub(crate) fn pixel_size(&self) -> f32 {
+        self.pixel_size
+    }
+}
+
+/// Location of a rasterized glyph in the atlas.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct AtlasGlyph {
+    /// Offset of the glyph's top left corner from its origin on the baseline, in pixels, y down.
+    pub offset: Vec2,
+    /// Size of the glyph in pixels.
+    pub size: Vec2,
+    /// Texture coordinates of the top left and bottom right corners.
+    pub tex_min: Vec2,
+    pub tex_max: Vec2,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct GlyphKey {
+    font: usize,
+    glyph: u16,
+    /// Pixel size as raw bits so it can be hashed.
+    pixel_size: u32,
+}
+
+/// CPU side of the glyph atlas, the renderer uploads the image when it is created and
+/// writes the texels of newly rasterized glyphs into the existing texture.
+///
+/// The atlas stores white pixels with the glyph coverage in the alpha channel,
+/// so glyphs are colored by the vertex color they are drawn with.
+pub(crate) struct GlyphAtlas {
+    packer: ShelfPacker,
+    image: Option<image::RgbaImage>,
+    glyphs: HashMap<GlyphKey, Option<AtlasGlyph>>,
+    version: u64,
+    /// Part of the image with glyphs added since the renderer last uploaded it.
+    dirty: Option<TexelRect>,
+}
+
+impl GlyphAtlas {
+    pub fn new() -> Self {
+        Self {
+            packer: ShelfPacker::new(ATLAS_SIZE, ATLAS_SIZE, 1),
+            image: None,
+            glyphs: HashMap::new(),
+            version: 0,
+            dirty: None,
+        }
+    }
+
+    /// Changes when the image is created.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Part of the image that changed since [`clear_dirty`](#method.clear_dirty) was last called.
+    pub fn dirty(&self) -> Option<TexelRect> {
+        self.dirty
+    }
+
+    pub fn clear_dirty(&mut self) {
+        self.dirty = None;
+    }
+
+    /// The atlas image, `None` until the first glyph is rasterized.
+    pub fn image(&self) -> Option<&image::RgbaImage> {
+        self.image.as_ref()
+    }
+
+    /// Returns the glyph from the atlas, rasterizing it first if needed.
+    ///
+    /// Glyphs without an outline, like spaces, or that don't fit in the atlas return `None`.
+    pub fn glyph(&mut self, font: &Font, id: GlyphId) -> Option<AtlasGlyph> {
+        let key = GlyphKey {
+            font: font.id,
+            glyph: id.0,
+            pixel_size: font.pixel_size.to_bits(),
+        };
+        if let Some(glyph) = self.glyphs.get(&key) {
+            return *glyph;
+        }
+        let glyph = self.rasterize(font, id);
+        self.glyphs.insert(key, glyph);
+        glyph
+    }
+
+    fn rasterize(&mut self, font: &Font, id: GlyphId) -> Option<AtlasGlyph> {
+        let outlined = font.font.outline_glyph(id.with_scale(font.pixel_size))?;
+        let bounds = outlined.px_bounds();
+        let width = bounds.width().ceil() as u32;
+        let height = bounds.height().ceil() as u32;
+        let (x, y) = match self.packer.pack(width, height) {
+            Some(position) => position,
+            None => {
+                log::warn!("Glyph atlas is full, glyph {:?} will not be drawn", id);
+                return None;
+            }
+        };
+        if self.image.is_none() {
+            self.image = Some(image::RgbaImage::new(ATLAS_SIZE, ATLAS_SIZE));
+            self.version = texture::next_version();
+        }
+        let image = self.image.as_mut().unwrap();
+        outlined.draw(|gx, gy, coverage| {
+            let alpha = (coverage.min(1.0) * 255.0).round() as u8;
+            image.put_pixel(x + gx, y + gy, image::Rgba([255, 255, 255, alpha]));
+        });
+        let rect = TexelRect {
+            x,
+            y,
+            width,
+            height,
+        };
+        self.dirty = Some(match self.dirty {
+            Some(dirty) => dirty.union(rect),
+            None => rect,
+        });
+
+        let (atlas_width, atlas_height) = self.packer.size();
+        Some(AtlasGlyph {
+            offset: [bounds.min.x, bounds.min.y],
+            size: [width as f32, height as f32],
+            tex_min: [
+                x as f32 / atlas_width as f32,
+                y as f32 / atlas_height as f32,
+            ],
+            tex_max: [
+                (x + width) as f32 / atlas_width as f32,
+                (y + height) as f32 / atlas_height as f32,
+            ],
+        })
+    }
+}
+
+impl std::fmt::Debug for GlyphAtlas {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // The image is too large to be useful in debug output
+        f.debug_struct("GlyphAtlas")
+            .field("glyphs", &self.glyphs.len())
+            .field("version", &self.version)
+            .finish()
+    }
+}
+
+/// Horizontal alignment of the lines in a block of text.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// Describes how a block of proportional text is laid out.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TextStyle {
+    /// Height of an em in canvas units.
+    pub size: f32,
+    /// Lines are wrapped between words to fit in this width, words that don't fit on a line
+    /// of their own are broken between characters.
+    pub max_width: Option<f32>,
+    pub align: Align,
+    /// Multiplier of the font's line height.
+    pub line_spacing: f32,
+}
+
+impl TextStyle {
+    pub fn new(size: f32) -> Self {
+        Self {
+            size,
+            max_width: None,
+            align: Align::Left,
+            line_spacing: 1.0,
+        }
+    }
+
+    pub fn with_max_width(mut self, max_width: f32) -> Self {
+        self.max_width = Some(max_width);
+        self
+    }
+
+    pub fn with_align(mut self, align: Align) -> Self {
+        self.align = align;
+        self
+    }
+
+    pub fn with_line_spacing(mut self, line_spacing: f32) -> Self {
+        self.line_spacing = line_spacing;
+        self
+    }
+}
+
+/// A block of text added to a canvas.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TextBlock {
+    pub mesh: Mesh,
+    /// Area covered by the lines of text from the top of the first line to the bottom of the last,
+    /// in canvas units.
+    pub bounds: Rect,
+}
+
+/// Glyphs positioned by [`layout`](fn.layout.html).
+#[derive(Clone, Debug)]
+pub(crate) struct Layout {
+    /// Glyphs and the position of their origin on the baseline in pixels, y down,
+    /// relative to the start of the first baseline.
+    pub glyphs: Vec<(GlyphId, Vec2)>,
+    /// Horizontal extent of the lines in pixels.
+    left: f32,
+    right: f32,
+    lines: usize,
+    line_height: f32,
+}
+
+impl Layout {
+    /// Bounding box of the lines in canvas units, relative to the start of the first baseline.
+    pub fn bounds(&self, font: &Font, size: f32) -> Rect {
+        let scale = size / font.pixel_size;
+        let scaled = font.font.as_scaled(font.pixel_size);
+        let top = scaled.ascent();
+        let bottom = (self.lines.max(1) - 1) as f32 * self.line_height - scaled.descent();
+        Rect {
+            min: [self.left * scale, -bottom * scale],
+            max: [self.right * scale, top * scale],
+        }
+    }
+}
+
+/// Measures the bounding box of the text laid out with the style, in canvas units,
+/// relative to the start of the first baseline.
+pub fn measure(font: &Font, text: &str, style: &TextStyle) -> Rect {
+    layout(font, text, style).bounds(font, style.size)
+}
+
+/// Lays out proportional text with kerning, wrapping and alignment.
+pub(crate) fn layout(font: &Font, text: &str, style: &TextStyle) -> Layout {
+    let scaled = font.font.as_scaled(font.pixel_size);
+    let scale = style.size / font.pixel_size;
+    let max_width = style.max_width.map(|max_width| max_width / scale);
+    let line_height = (scaled.height() + scaled.line_gap()) * style.line_spacing;
+
+    // Break the text into lines of glyphs positioned from the start of the line
+    let fits = |width: f32| match max_width {
+        Some(max_width) => width <= max_width,
+        None => true,
+    };
+    // Glyphs with their position and whether they are whitespace
+    let mut lines: Vec<Vec<(GlyphId, f32, bool)>> = Vec::new();
+    let mut widths = Vec::new();
+    for paragraph in text.split('\n') {
+        let mut line = Vec::new();
+        let mut pen = 0.0;
+        let mut previous = None;
+        let mut wrapped = false;
+        for word in words(paragraph) {
+            let word_glyphs = word
+                .chars()
+                .filter(|c| !c.is_control())
+                .map(|c| scaled.glyph_id(c))
+                .collect::<Vec<_>>();
+            let is_space = word.starts_with(char::is_whitespace);
+            if is_space && wrapped && line.is_empty() {
+                // Whitespace at a wrap is dropped
+                continue;
+            }
+            if !is_space
+                && !line.is_empty()
+                && !fits(pen + advance(&scaled, previous, &word_glyphs))
+            {
+                widths.push(trimmed_width(&scaled, &line));
+                lines.push(mem::take(&mut line));
+                pen = 0.0;
+                previous = None;
+                wrapped = true;
+            }
+            for id in word_glyphs {
+                if let Some(previous) = previous {
+                    pen += scaled.kern(previous, id);
+                }
+                // Words wider than a whole line are broken between characters
+                if !is_space && !line.is_empty() && !fits(pen + scaled.h_advance(id)) {
+                    widths.push(trimmed_width(&scaled, &line));
+                    lines.push(mem::take(&mut line));
+                    pen = 0.0;
+                    wrapped = true;
+                }
+                line.push((id, pen, is_space));
+                pen += scaled.h_advance(id);
+                previous = Some(id);
+            }
+        }
+        widths.push(trimmed_width(&scaled, &line));
+        lines.push(line);
+    }
+
+    let block_width = max_width.unwrap_or_else(|| widths.iter().cloned().fold(0.0, f32::max));
+    let mut glyphs = Vec::with_capacity(text.len());
+    let mut left = f32::MAX;
+    let mut right = f32::MIN;
+    for (i, (line, width)) in lines.iter().zip(widths.iter()).enumerate() {
+        let offset = match style.align {
+            Align::Left => 0.0,
+            Align::Center => (block_width - width) * 0.5,
+            Align::Right => block_width - width,
+        };
+        left = left.min(offset);
+        right = right.max(offset + width);
+        let baseline = i as f32 * line_height;
+        glyphs.extend(line.iter().map(|(id, x, _)| (*id, [offset + x, baseline])));
+    }
+    Layout {
+        glyphs,
+        left,
+        right,
+        lines: lines.len(),
+        line_height,
+    }
+}
+
+/// Splits the text into alternating runs of whitespace and non-whitespace.
+fn words(text: &str) -> impl Iterator<Item = &str> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        let first = rest.chars().next()?;
+        let space = first.is_whitespace();
+        let end = rest
+            .char_indices()
+            .find(|(_, c)| c.is_whitespace() != space)
+            .map_or(rest.len(), |(i, _)| i);
+        let (word, remainder) = rest.split_at(end);
+        rest = remainder;
+        Some(word)
+    })
+}
+
+/// Width of the glyphs including kerning with the glyph before them.
+fn advance<F: ab_glyph::Font>(
+    scaled: &ab_glyph::PxScaleFont<F>,
+    mut previous: Option<GlyphId>,
+    glyphs: &[GlyphId],
+) -> f32 {
+    let mut width = 0.0;
+    for id in glyphs {
+        if let Some(previous) = previous {
+            width += scaled.kern(previous, *id);
+        }
+        width += scaled.h_advance(*id);
+        previous = Some(*id);
+    }
+    width
+}
+
+/// Width of the line without trailing whitespace, which shouldn't affect alignment.
+fn trimmed_width<F: ab_glyph::Font>(
+    scaled: &ab_glyph::PxScaleFont<F>,
+    line: &[(GlyphId, f32, bool)],
+) -> f32 {
+    line.iter()
+        .rev()
+        .find(|(_, _, is_space)| !is_space)
+        .map_or(0.0, |(id, x, _)| x + scaled.h_advance(*id))
+}
+
+/// Lays out the text on a grid of equally wide cells, as with a monospaced font.
+///
+/// Returns the glyphs to draw and the position of their origin on the baseline in pixels,
+/// y down, relative to the origin of the first line.
+pub(crate) fn layout_monospaced(font: &Font, text: &str) -> Vec<(GlyphId, Vec2)> {
+    let scaled = font.font.as_scaled(font.pixel_size);
+    let advance = scaled.h_advance(scaled.glyph_id('M'));
+    let line_height = scaled.height() + scaled.line_gap();
+    let mut glyphs = Vec::with_capacity(text.len());
+    let mut column = 0;
+    let mut line = 0;
+    for c in text.chars() {
+        match c {
+            '\n' => {
+                column = 0;
+                line += 1;
+            }
+            '\t' => column += 4 - column % 4,
+            c if c.is_control() => {}
+            c => {
+                let position = [column as f32 * advance, line as f32 * line_height];
+                glyphs.push((scaled.glyph_id(c), position));
+                column += 1;
+            }
+        }
+    }
+    glyphs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mono() -> Font {
+        Font::from_bytes(include_bytes!("../../tests/fonts/DejaVuSansMono.ttf").to_vec()).unwrap()
+    }
+
+    fn sans() -> Font {
+        Font::from_bytes(include_bytes!("../../tests/fonts/DejaVuSans.ttf").to_vec()).unwrap()
+    }
+
+    /// Style that measures in pixels of the font, so positions can be compared with its metrics.
+    fn pixels(font: &Font) -> TextStyle {
+        TextStyle::new(font.pixel_size())
+    }
+
+    fn x_positions(layout: &Layout) -> Vec<f32> {
+        layout.glyphs.iter().map(|(_, [x, _])| *x).collect()
+    }
+
+    fn baselines(layout: &Layout) -> Vec<f32> {
+        let mut baselines = layout
+            .glyphs
+            .iter()
+            .map(|(_, [_, y])| *y)
+            .collect::<Vec<_>>();
+        baselines.dedup();
+        baselines
+    }
+
+    #[test]
+    fn kerns_pairs() {
+        let font = sans();
+        let scaled = font.font().as_scaled(font.pixel_size());
+        let (a, v) = (scaled.glyph_id('A'), scaled.glyph_id('V'));
+        assert!(scaled.kern(a, v) < 0.0);
+        let layout = layout(&font, "AV", &pixels(&font));
+        assert_eq!(
+            x_positions(&layout),
+            vec![0.0, scaled.h_advance(a) + scaled.kern(a, v)]
+        );
+    }
+
+    #[test]
+    fn wraps_between_words() {
+        let font = sans();
+        let style = pixels(&font);
+        let scaled = font.font().as_scaled(font.pixel_size());
+        let line_height = scaled.height() + scaled.line_gap();
+        let word = measure(&font, "word", &style).max[0];
+
+        let unwrapped = layout(&font, "word word word", &style);
+        assert_eq!(baselines(&unwrapped), vec![0.0]);
+        // Two words and the space between them fit on a line
+        let wrapped = layout(&font, "word word word", &style.with_max_width(word * 2.5));
+        assert_eq!(baselines(&wrapped), vec![0.0, line_height]);
+        // The space at the wrap is dropped, so the third word starts the second line
+        let second_line = wrapped.glyphs.iter().filter(|(_, [_, y])| *y > 0.0);
+        assert_eq!(second_line.map(|(_, [x, _])| *x).next(), Some(0.0));
+
+        // Words wider than the line are broken between characters
+        let broken = layout(&font, "wordword", &style.with_max_width(word));
+        assert_eq!(baselines(&broken), vec![0.0, line_height]);
+        // Explicit line breaks are kept
+        let lines = layout(&font, "a\n\nb", &style);
+        assert_eq!(baselines(&lines), vec![0.0, 2.0 * line_height]);
+    }
+
+    #[test]
+    fn aligns_lines_without_trailing_whitespace() {
+        let font = sans();
+        let style = pixels(&font);
+        let short = measure(&font, "ab", &style).max[0];
+        let long = measure(&font, "abab", &style).max[0];
+
+        let center = layout(&font, "abab\nab", &style.with_align(Align::Center));
+        assert_eq!(center.glyphs[4].1[0], (long - short) * 0.5);
+        let right = layout(&font, "abab\nab", &style.with_align(Align::Right));
+        assert_eq!(right.glyphs[4].1[0], long - short);
+
+        // Trailing whitespace of any kind doesn't push the line away from the edge
+        for text in &["ab \nabab", "ab\t\nabab", "ab\u{a0}\u{3000}\nabab"] {
+            let right = layout(&font, text, &style.with_align(Align::Right));
+            assert_eq!(right.glyphs[0].1[0], long - short, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn measures_lines() {
+        let font = sans();
+        let style = TextStyle::new(1.0);
+        let scaled = font.font().as_scaled(font.pixel_size());
+        let scale = 1.0 / font.pixel_size();
+        let line_height = (scaled.height() + scaled.line_gap()) * scale;
+
+        let one = measure(&font, "Hello", &style);
+        let two = measure(&font, "Hello\nHello", &style);
+        assert!((one.max[1] - scaled.ascent() * scale).abs() < 1e-6);
+        assert!((one.min[1] - scaled.descent() * scale).abs() < 1e-6);
+        assert!((one.height() + line_height - two.height()).abs() < 1e-5);
+        assert_eq!(one.width(), two.width());
+        let spaced = measure(&font, "Hello\nHello", &style.with_line_spacing(2.0));
+        assert!((spaced.height() - two.height() - line_height).abs() < 1e-5);
+    }
+
+    #[test]
+    fn monospaced_layout_uses_cells() {
+        let font = mono();
+        let scaled = font.font().as_scaled(font.pixel_size());
+        let advance = scaled.h_advance(scaled.glyph_id('M'));
+        let line_height = scaled.height() + scaled.line_gap();
+
+        let glyphs = layout_monospaced(&font, "ab\ni\tj\r");
+        let positions = glyphs.iter().map(|(_, p)| *p).collect::<Vec<_>>();
+        assert_eq!(
+            positions,
+            vec![
+                [0.0, 0.0],
+                [advance, 0.0],
+                [0.0, line_height],
+                // The tab moves to the next multiple of 4 cells, control characters are skipped
+                [4.0 * advance, line_height],
+            ]
+        );
+        assert_eq!(glyphs[0].0, scaled.glyph_id('a'));
+        assert_eq!(glyphs[3].0, scaled.glyph_id('j'));
+    }
+
+    #[test]
+    fn atlas_rasterizes_glyphs_once() {
+        let font = mono();
+        let mut atlas = GlyphAtlas::new();
+        assert!(atlas.image().is_none());
+        let space = font.font().glyph_id(' ');
+        assert!(atlas.glyph(&font, space).is_none());
+        assert!(atlas.image().is_none());
+
+        let id = font.font().glyph_id('A');
+        let glyph = atlas.glyph(&font, id).unwrap();
+        assert_ne!(atlas.version(), 0);
+        let dirty = atlas.dirty().unwrap();
+        assert_eq!([dirty.width as f32, dirty.height as f32], glyph.size);
+        // The glyph's texels are white with its coverage in the alpha channel
+        let image = atlas.image().unwrap();
+        let x = (glyph.tex_min[0] * ATLAS_SIZE as f32) as u32;
+        let y = (glyph.tex_min[1] * ATLAS_SIZE as f32) as u32;
+        let covered = (0..glyph.size[1] as u32)
+            .flat_map(|gy| (0..glyph.size[0] as u32).map(move |gx| (x + gx, y + gy)))
+            .filter(|(px, py)| image.get_pixel(*px, *py).0[3] > 0)
+            .count();
+        assert!(covered > 0);
+        assert!(image
+            .pixels()
+            .all(|p| p.0[..3] == [255, 255, 255] || p.0[3] == 0));
+
+        atlas.clear_dirty();
+        let version = atlas.version();
+        let again = atlas.glyph(&font, id).unwrap();
+        assert_eq!(again.tex_min, glyph.tex_min);
+        assert!(atlas.dirty().is_none());
+        assert_eq!(atlas.version(), version);
+
+        // Another size is another glyph
+        let large = font.clone().with_pixel_size(96.0);
+        let large_glyph = atlas.glyph(&large, id).unwrap();
+        assert!(large_glyph.size[0] > glyph.size[0]);
+        assert!(atlas.dirty().is_some());
+    }
+}
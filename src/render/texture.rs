@@ -1,10 +1,264 @@
 use crate::render::error::Error;
-use image::GenericImageView;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Largest width or height of a texture, the limit every wgpu backend supports.
+pub const MAX_TEXTURE_SIZE: u32 = 8192;
+
+static NEXT_VERSION: AtomicU64 = AtomicU64::new(1);
+
+/// Handle to a texture stored in a [`Canvas`].
+///
+/// Handles only work with the canvas that created them, except for the built in textures
+/// which every canvas has.
+///
+/// [`Canvas`]: ../mesh/struct.Canvas.html
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TextureHandle {
+    /// Id of the canvas the texture was added to, 0 for the built in textures.
+    canvas: u64,
+    index: u32,
+}
+
+impl TextureHandle {
+    /// A single white pixel, meshes sample it until they are given a texture of their own.
+    pub const WHITE: Self = Self {
+        canvas: 0,
+        index: 0,
+    };
+    /// The atlas that the canvas rasterizes text glyphs into.
+    pub(crate) const GLYPHS: Self = Self {
+        canvas: 0,
+        index: 1,
+    };
+    /// Index of the first texture added to a canvas.
+    pub(crate) const FIRST: u32 = 2;
+
+    pub(crate) fn new(canvas: u64, index: u32) -> Self {
+        Self { canvas, index }
+    }
+
+    pub(crate) fn canvas(self) -> u64 {
+        self.canvas
+    }
+
+    pub(crate) fn index(self) -> usize {
+        self.index as usize
+    }
+}
+
+impl Default for TextureHandle {
+    fn default() -> Self {
+        Self::WHITE
+    }
+}
+
+/// How a texture is sampled between its texels.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Filter {
+    /// Uses the closest texel, keeping pixel art and data grids sharp.
+    Nearest,
+    /// Blends the closest texels, smoothing scaled images.
+    Linear,
+}
+
+/// How a texture is sampled outside of texture coordinates 0 to 1.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Wrap {
+    /// Repeats the edge texels.
+    Clamp,
+    /// Tiles the texture.
+    Repeat,
+    /// Tiles the texture, flipping every other tile.
+    Mirror,
+}
+
+/// Options for sampling a texture.
+///
+/// The default samples the nearest texel and clamps to the edges.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TextureOptions {
+    /// Filter used where the texture is drawn larger than its size.
+    pub mag_filter: Filter,
+    /// Filter used where the texture is drawn smaller than its size.
+    pub min_filter: Filter,
+    pub wrap_u: Wrap,
+    pub wrap_v: Wrap,
+    /// Maximum anisotropy, one of 1, 2, 4, 8 or 16, used to sharpen textures viewed at an angle.
+    /// Ignored on devices without the `SAMPLER_ANISOTROPY` feature.
+    pub anisotropy: Option<u8>,
+    /// Generates a chain of halved images when the texture is created, so it doesn't shimmer
+    /// or alias where it is drawn smaller than its size. Levels are blended with the min filter.
+    pub mipmaps: bool,
+    /// Keeps two copies of the texture on the GPU and writes updates to the one not drawn
+    /// last frame, for images that change every frame such as video or simulation output.
+    pub double_buffered: bool,
+}
+
+impl Default for TextureOptions {
+    fn default() -> Self {
+        Self {
+            mag_filter: Filter::Nearest,
+            min_filter: Filter::Nearest,
+            wrap_u: Wrap::Clamp,
+            wrap_v: Wrap::Clamp,
+            anisotropy: None,
+            mipmaps: false,
+            double_buffered: false,
+        }
+    }
+}
+
+impl TextureOptions {
+    /// Samples the nearest texel, for pixel art and data that must not be blurred.
+    pub fn nearest() -> Self {
+        Self::default()
+    }
+
+    /// Blends neighbouring texels, for images that are drawn at different sizes.
+    pub fn linear() -> Self {
+        Self::default().with_filter(Filter::Linear)
+    }
+
+    /// Sets both the magnification and minification filter.
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.mag_filter = filter;
+        self.min_filter = filter;
+        self
+    }
+
+    pub fn with_mag_filter(mut self, filter: Filter) -> Self {
+        self.mag_filter = filter;
+        self
+    }
+
+    pub fn with_min_filter(mut self, filter: Filter) -> Self {
+        self.min_filter = filter;
+        self
+    }
+
+    /// Sets how both texture coordinates wrap.
+    pub fn with_wrap(mut self, wrap: Wrap) -> Self {
+        self.wrap_u = wrap;
+        self.wrap_v = wrap;
+        self
+    }
+
+    pub fn with_wrap_u(mut self, wrap: Wrap) -> Self {
+        self.wrap_u = wrap;
+        self
+    }
+
+    pub fn with_wrap_v(mut self, wrap: Wrap) -> Self {
+        self.wrap_v = wrap;
+        self
+    }
+
+    pub fn with_anisotropy(mut self, anisotropy: u8) -> Self {
+        self.anisotropy = Some(anisotropy);
+        self
+    }
+
+    pub fn with_mipmaps(mut self, mipmaps: bool) -> Self {
+        self.mipmaps = mipmaps;
+        self
+    }
+
+    pub fn with_double_buffering(mut self, double_buffered: bool) -> Self {
+        self.double_buffered = double_buffered;
+        self
+    }
+
+    /// Textures that wrap need a texture of their own rather than a region of an atlas page,
+    /// as do mipmapped textures whose smaller levels would blend neighbouring regions.
+    pub(crate) fn packable(&self) -> bool {
+        self.wrap_u == Wrap::Clamp && self.wrap_v == Wrap::Clamp && !self.mipmaps
+    }
+
+    /// Anisotropy is left out if the device doesn't support it.
+    fn sampler_descriptor(&self, features: wgpu::Features) -> wgpu::SamplerDescriptor<'static> {
+        let anisotropy = if features.contains(wgpu::Features::SAMPLER_ANISOTROPY) {
+            self.anisotropy
+        } else {
+            None
+        };
+        wgpu::SamplerDescriptor {
+            address_mode_u: self.wrap_u.into(),
+            address_mode_v: self.wrap_v.into(),
+            mag_filter: self.mag_filter.into(),
+            min_filter: self.min_filter.into(),
+            mipmap_filter: self.min_filter.into(),
+            anisotropy_clamp: anisotropy,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<Filter> for wgpu::FilterMode {
+    fn from(filter: Filter) -> Self {
+        match filter {
+            Filter::Nearest => wgpu::FilterMode::Nearest,
+            Filter::Linear => wgpu::FilterMode::Linear,
+        }
+    }
+}
+
+impl From<Wrap> for wgpu::AddressMode {
+    fn from(wrap: Wrap) -> Self {
+        match wrap {
+            Wrap::Clamp => wgpu::AddressMode::ClampToEdge,
+            Wrap::Repeat => wgpu::AddressMode::Repeat,
+            Wrap::Mirror => wgpu::AddressMode::MirrorRepeat,
+        }
+    }
+}
+
+/// A rectangle of texels, used to track which part of a texture has changed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct TexelRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl TexelRect {
+    /// Covers the whole image.
+    pub fn of(image: &image::RgbaImage) -> Self {
+        let (width, height) = image.dimensions();
+        Self {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        }
+    }
+
+    /// Returns the smallest rectangle covering both rectangles.
+    pub fn union(self, other: Self) -> Self {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Self {
+            x,
+            y,
+            width: (self.x + self.width).max(other.x + other.width) - x,
+            height: (self.y + self.height).max(other.y + other.height) - y,
+        }
+    }
+}
+
+/// Returns a version number that is unique across all canvases, so a renderer never mistakes
+/// the contents of a new texture for one it has already uploaded.
+pub(crate) fn next_version() -> u64 {
+    NEXT_VERSION.fetch_add(1, Ordering::Relaxed)
+}
 
 pub struct Texture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
+    pub width: u32,
+    pub height: u32,
 }
 
 impl Texture {
@@ -12,59 +266,282 @@ impl Texture {
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         bytes: &[u8],
+        options: &TextureOptions,
         label: &str,
     ) -> Result<Self, Error> {
-        let img = image::load_from_memory(bytes)?;
-        Self::from_image(device, queue, &img, Some(label))
+        let rgba = decode(bytes)?;
+        check_size(&rgba)?;
+        check_options(options)?;
+        Ok(Self::from_rgba(device, queue, &rgba, options, Some(label)))
+    }
+
+    /// Loads the image file at the path, in any format and color type that can be decoded.
+    pub fn from_path<P: AsRef<Path>>(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: P,
+        options: &TextureOptions,
+    ) -> Result<Self, Error> {
+        let label = path.as_ref().to_string_lossy().into_owned();
+        let rgba = open(path)?;
+        check_size(&rgba)?;
+        check_options(options)?;
+        Ok(Self::from_rgba(device, queue, &rgba, options, Some(&label)))
     }
 
     pub fn from_image(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         img: &image::DynamicImage,
+        options: &TextureOptions,
         label: Option<&str>,
     ) -> Result<Self, Error> {
-        let rgba = img.as_rgba8().unwrap();
-        let dimensions = img.dimensions();
+        // Images that aren't already 8-bit RGBA, like RGB, grayscale or 16-bit PNGs, are converted
+        let converted;
+        let rgba = match img.as_rgba8() {
+            Some(rgba) => rgba,
+            None => {
+                converted = img.to_rgba();
+                &converted
+            }
+        };
+        check_size(rgba)?;
+        check_options(options)?;
+        Ok(Self::from_rgba(device, queue, rgba, options, label))
+    }
+
+    /// Creates a texture from an image and options that have already been checked with
+    /// [`check_size`] and [`check_options`].
+    ///
+    /// [`check_size`]: fn.check_size.html
+    /// [`check_options`]: fn.check_options.html
+    pub fn from_rgba(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        rgba: &image::RgbaImage,
+        options: &TextureOptions,
+        label: Option<&str>,
+    ) -> Self {
+        let dimensions = rgba.dimensions();
 
         let size = wgpu::Extent3d {
             width: dimensions.0,
             height: dimensions.1,
             depth: 1,
         };
+        let mip_level_count = if options.mipmaps {
+            mip_level_count(dimensions.0, dimensions.1)
+        } else {
+            1
+        };
 
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label,
             size,
-            mip_level_count: 1,
+            mip_level_count,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format: wgpu::TextureFormat::Rgba8UnormSrgb,
             usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
         });
 
-        queue.write_texture(
-            wgpu::TextureCopyView {
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-            },
-            rgba,
-            wgpu::TextureDataLayout {
-                offset: 0,
-                bytes_per_row: 4 * dimensions.0,
-                rows_per_image: dimensions.1,
-            },
-            size,
-        );
+        write_texels(queue, &texture, 0, rgba, TexelRect::of(rgba), (0, 0));
+        // Each level is downsampled from the one before it on the CPU
+        let mut level = None;
+        for mip_level in 1..mip_level_count {
+            let next = downsample(level.as_ref().unwrap_or(rgba));
+            write_texels(
+                queue,
+                &texture,
+                mip_level,
+                &next,
+                TexelRect::of(&next),
+                (0, 0),
+            );
+            level = Some(next);
+        }
 
         let view = texture.create_default_view();
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+        let sampler = device.create_sampler(&options.sampler_descriptor(device.features()));
 
-        Ok(Self {
+        Self {
             texture,
             view,
             sampler,
+            width: dimensions.0,
+            height: dimensions.1,
+        }
+    }
+
+    /// Copies the rectangle of an image the size of the texture to the same place in the texture.
+    pub(crate) fn write_rect(&self, queue: &wgpu::Queue, rgba: &image::RgbaImage, rect: TexelRect) {
+        write_texels(queue, &self.texture, 0, rgba, rect, (rect.x, rect.y));
+    }
+}
+
+/// Decodes the contents of an image file, in any format and color type the `image` crate supports,
+/// into 8-bit RGBA.
+pub fn decode(bytes: &[u8]) -> Result<image::RgbaImage, Error> {
+    Ok(image::load_from_memory(bytes)?.to_rgba())
+}
+
+/// Opens and decodes the image file at the path into 8-bit RGBA.
+pub fn open<P: AsRef<Path>>(path: P) -> Result<image::RgbaImage, Error> {
+    Ok(image::open(path)?.to_rgba())
+}
+
+/// Returns an error if a texture can't be created with the size of the image.
+pub fn check_size(image: &image::RgbaImage) -> Result<(), Error> {
+    let (width, height) = image.dimensions();
+    let valid = |side| side > 0 && side <= MAX_TEXTURE_SIZE;
+    if valid(width) && valid(height) {
+        Ok(())
+    } else {
+        Err(Error::TextureSize {
+            width,
+            height,
+            max: MAX_TEXTURE_SIZE,
         })
     }
 }
+
+/// Returns an error if a texture can't be sampled with the options.
+pub fn check_options(options: &TextureOptions) -> Result<(), Error> {
+    match options.anisotropy {
+        Some(anisotropy) if ![1, 2, 4, 8, 16].contains(&anisotropy) => {
+            Err(Error::Anisotropy(anisotropy))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Number of levels in a full mipmap chain, down to a single texel.
+fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+/// Halves the size of the image, each texel is the average of up to 2x2 texels.
+///
+/// Colors are averaged in linear space, averaging the sRGB values would darken the level.
+fn downsample(image: &image::RgbaImage) -> image::RgbaImage {
+    let (width, height) = image.dimensions();
+    image::RgbaImage::from_fn((width / 2).max(1), (height / 2).max(1), |x, y| {
+        let xs = (x * 2)..(x * 2 + 2).min(width);
+        let ys = (y * 2)..(y * 2 + 2).min(height);
+        let mut sum = [0.0; 4];
+        let mut count = 0.0;
+        for sy in ys {
+            for sx in xs.clone() {
+                let image::Rgba(texel) = image.get_pixel(sx, sy);
+                for channel in 0..3 {
+                    sum[channel] += srgb_to_linear(texel[channel]);
+                }
+                sum[3] += texel[3] as f32 / 255.0;
+                count += 1.0;
+            }
+        }
+        let channel = |c: usize, value: f32| {
+            let value = if c < 3 { linear_to_srgb(value) } else { value };
+            (value * 255.0).round() as u8
+        };
+        image::Rgba([
+            channel(0, sum[0] / count),
+            channel(1, sum[1] / count),
+            channel(2, sum[2] / count),
+            channel(3, sum[3] / count),
+        ])
+    })
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let value = value as f32 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Copies the rectangle of the image into the mip level with its top left corner at the origin.
+fn write_texels(
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    mip_level: u32,
+    rgba: &image::RgbaImage,
+    rect: TexelRect,
+    (x, y): (u32, u32),
+) {
+    // The rows of the rectangle are read straight out of the image, no copy is needed
+    let bytes_per_row = 4 * rgba.width();
+    queue.write_texture(
+        wgpu::TextureCopyView {
+            texture,
+            mip_level,
+            origin: wgpu::Origin3d { x, y, z: 0 },
+        },
+        rgba,
+        wgpu::TextureDataLayout {
+            offset: (rect.y * bytes_per_row + rect.x * 4) as wgpu::BufferAddress,
+            bytes_per_row,
+            rows_per_image: rect.height,
+        },
+        wgpu::Extent3d {
+            width: rect.width,
+            height: rect.height,
+            depth: 1,
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anisotropy_needs_the_feature() {
+        let options = TextureOptions::linear().with_anisotropy(8);
+        let supported = options.sampler_descriptor(wgpu::Features::SAMPLER_ANISOTROPY);
+        assert_eq!(supported.anisotropy_clamp, Some(8));
+        let unsupported = options.sampler_descriptor(wgpu::Features::empty());
+        assert_eq!(unsupported.anisotropy_clamp, None);
+    }
+
+    #[test]
+    fn downsamples_in_linear_space() {
+        let checker = image::RgbaImage::from_fn(4, 2, |x, y| {
+            let v = if (x + y) % 2 == 0 { 255 } else { 0 };
+            image::Rgba([v, v, v, 255])
+        });
+        let level = downsample(&checker);
+        assert_eq!(level.dimensions(), (2, 1));
+        // Half of the light in sRGB rather than the darker 128
+        assert_eq!(level.get_pixel(0, 0), &image::Rgba([188, 188, 188, 255]));
+
+        let level = downsample(&image::RgbaImage::from_pixel(5, 1, image::Rgba([10; 4])));
+        assert_eq!(level.dimensions(), (2, 1));
+        assert_eq!(level.get_pixel(1, 0), &image::Rgba([10; 4]));
+    }
+
+    #[test]
+    fn checks_anisotropy() {
+        for &anisotropy in &[1, 2, 4, 8, 16] {
+            let options = TextureOptions::linear().with_anisotropy(anisotropy);
+            assert!(check_options(&options).is_ok());
+        }
+        for &anisotropy in &[0, 3, 32] {
+            let options = TextureOptions::linear().with_anisotropy(anisotropy);
+            assert!(matches!(
+                check_options(&options),
+                Err(Error::Anisotropy(a)) if a == anisotropy
+            ));
+        }
+    }
+}
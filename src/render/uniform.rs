@@ -14,8 +14,9 @@ impl Uniform {
         }
     }
 
-    pub fn update_view_proj(&mut self, camera: &Camera) {
-        self.view_proj = camera.build_view_projection_matrix();
+    /// Updates the view projection for a render target with the size.
+    pub fn update_view_proj(&mut self, camera: &Camera, width: u32, height: u32) {
+        self.view_proj = camera.view_projection(width, height);
     }
 }
 
@@ -0,0 +1,830 @@
+use maple::render::camera::ProjectionMode;
+use maple::render::error::Error;
+use maple::render::framebuffer::{Framebuffer, Scaling};
+use maple::render::golden::Golden;
+use maple::render::headless::Headless;
+use maple::render::math::Rect;
+use maple::render::mesh::{Canvas, MeshBuilder};
+use maple::render::path::{FillRule, Path};
+use maple::render::sprite::Animation;
+use maple::render::stroke::{LineCap, LineJoin, StrokeStyle};
+use maple::render::svg::Svg;
+use maple::render::text::Font;
+use maple::render::texture::{TextureHandle, TextureOptions, Wrap};
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 64;
+
+/// Returns `None` when the machine has no graphics adapter, e.g. a CI runner without a GPU.
+fn try_headless() -> Option<Headless> {
+    let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
+    match futures::executor::block_on(Headless::new(&instance, WIDTH, HEIGHT)) {
+        Ok(headless) => Some(headless),
+        Err(Error::AdapterNotFound) => {
+            eprintln!("Skipping test, no graphics adapter is available");
+            None
+        }
+        Err(error) => panic!("Failed to create headless renderer: {}", error),
+    }
+}
+
+/// Creates a headless renderer, or returns from the test if it can't run on this machine.
+macro_rules! headless {
+    () => {
+        match try_headless() {
+            Some(headless) => headless,
+            None => return,
+        }
+    };
+}
+
+fn golden() -> Golden {
+    Golden::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden")).with_tolerance(2)
+}
+
+#[test]
+fn empty_canvas() {
+    let mut headless = headless!();
+    let image = headless.render().unwrap();
+    golden().check("empty_canvas", &image).unwrap();
+}
+
+#[test]
+fn colored_quads() {
+    let mut headless = headless!();
+    let canvas = &mut headless.canvas;
+    let red = canvas.quad(-0.5, 0.5, 0.5, 0.5);
+    canvas.color(&red, [1.0, 0.0, 0.0, 1.0]).unwrap();
+    let green = canvas.quad(0.5, 0.5, 0.5, 0.5);
+    canvas.color(&green, [0.0, 1.0, 0.0, 1.0]).unwrap();
+    let blue = canvas.quad(0.0, -0.5, 1.0, 0.5);
+    canvas.color(&blue, [0.0, 0.0, 1.0, 1.0]).unwrap();
+    let image = headless.render().unwrap();
+    golden().check("colored_quads", &image).unwrap();
+}
+
+#[test]
+fn deleted_quad() {
+    let mut headless = headless!();
+    let canvas = &mut headless.canvas;
+    let first = canvas.quad(-0.5, 0.0, 0.5, 0.5);
+    canvas.color(&first, [1.0, 1.0, 0.0, 1.0]).unwrap();
+    let second = canvas.quad(0.5, 0.0, 0.5, 0.5);
+    canvas.color(&second, [0.0, 1.0, 1.0, 1.0]).unwrap();
+    canvas.delete(&first).unwrap();
+    assert!(canvas.color(&first, [1.0, 0.0, 0.0, 1.0]).is_err());
+    canvas.color(&second, [1.0, 0.0, 1.0, 1.0]).unwrap();
+    let image = headless.render().unwrap();
+    golden().check("deleted_quad", &image).unwrap();
+}
+
+#[test]
+fn foreign_handles() {
+    let mut first = Canvas::new();
+    let mut second = Canvas::new();
+    let image = image::RgbaImage::new(2, 2);
+    let quad = first.quad(0.0, 0.0, 1.0, 1.0);
+    let texture = first
+        .add_texture(image.clone(), TextureOptions::default())
+        .unwrap();
+    // The second canvas has a mesh and a texture in the same places
+    let other_quad = second.quad(0.0, 0.0, 1.0, 1.0);
+    let other_texture = second
+        .add_texture(image.clone(), TextureOptions::default())
+        .unwrap();
+
+    assert!(!second.contains(&quad));
+    assert!(matches!(
+        second.color(&quad, [1.0, 0.0, 0.0, 1.0]),
+        Err(Error::MeshNotFound(_))
+    ));
+    assert!(!second.contains_texture(texture));
+    assert!(matches!(
+        second.texture(&other_quad, texture),
+        Err(Error::TextureNotFound(_))
+    ));
+    assert!(matches!(
+        second.update_texture(texture, 0, 0, &image),
+        Err(Error::TextureNotFound(_))
+    ));
+    assert!(second
+        .vertices()
+        .iter()
+        .all(|vertex| vertex.color == [1.0; 4]));
+
+    // Built in textures belong to every canvas
+    second.texture(&other_quad, other_texture).unwrap();
+    second.texture(&other_quad, TextureHandle::WHITE).unwrap();
+    first.texture(&quad, TextureHandle::WHITE).unwrap();
+}
+
+#[test]
+fn compaction_keeps_handles() {
+    let mut canvas = Canvas::new();
+    let quads = (0..8)
+        .map(|i| canvas.quad(i as f32, 0.0, 1.0, 1.0))
+        .collect::<Vec<_>>();
+    // Deleting more than half of the vertices compacts the canvas
+    for quad in &quads[..4] {
+        canvas.delete(quad).unwrap();
+    }
+    assert_eq!(canvas.vertices().len(), 8 * 4);
+    canvas.delete(&quads[4]).unwrap();
+    assert_eq!(canvas.vertices().len(), 3 * 4);
+    assert_eq!(canvas.transforms().len(), 3);
+
+    for (i, quad) in quads.iter().enumerate() {
+        if i < 5 {
+            assert!(!canvas.contains(quad));
+            assert!(matches!(
+                canvas.color(quad, [1.0; 4]),
+                Err(Error::MeshNotFound(_))
+            ));
+        } else {
+            assert!(canvas.contains(quad));
+            assert_eq!(
+                canvas.transform(quad).unwrap().translation,
+                [i as f32, 0.0, 0.0]
+            );
+        }
+    }
+    // Vertices still belong to the transforms of their meshes, in the order they were added
+    let owners = canvas
+        .vertices()
+        .iter()
+        .map(|vertex| canvas.transforms()[vertex.transform_index as usize].translation[0])
+        .collect::<Vec<_>>();
+    assert_eq!(owners, [[5.0; 4], [6.0; 4], [7.0; 4]].concat());
+    assert!(canvas
+        .indices()
+        .iter()
+        .all(|index| (*index as usize) < canvas.vertices().len()));
+
+    // Freed slots are reused, the old handles to them stay invalid
+    let added = canvas.quad(8.0, 0.0, 1.0, 1.0);
+    assert!(canvas.contains(&added));
+    assert!(quads[..5].iter().all(|quad| !canvas.contains(quad)));
+    assert_eq!(canvas.meshes().count(), 4);
+    canvas.delete(&quads[6]).unwrap();
+    canvas.compact();
+    assert_eq!(
+        canvas.transform(&added).unwrap().translation,
+        [8.0, 0.0, 0.0]
+    );
+    assert_eq!(
+        canvas.transform(&quads[7]).unwrap().translation,
+        [7.0, 0.0, 0.0]
+    );
+}
+
+#[test]
+fn incremental_updates() {
+    let mut headless = headless!();
+    let canvas = &mut headless.canvas;
+    let quads = (0..4)
+        .map(|i| canvas.quad(-0.75 + i as f32 * 0.5, 0.0, 0.4, 0.4))
+        .collect::<Vec<_>>();
+    for quad in &quads {
+        canvas.color(quad, [1.0, 0.0, 0.0, 1.0]).unwrap();
+    }
+    headless.render().unwrap();
+    // Nothing changed, nothing is uploaded
+    headless.render().unwrap();
+
+    // Only the changed ranges are uploaded, the rest must be kept from earlier frames
+    let canvas = &mut headless.canvas;
+    canvas.color(&quads[1], [0.0, 1.0, 0.0, 1.0]).unwrap();
+    canvas.delete(&quads[2]).unwrap();
+    let added = canvas.quad(0.0, 0.5, 0.4, 0.4);
+    canvas.color(&added, [0.0, 0.0, 1.0, 1.0]).unwrap();
+    let image = headless.render().unwrap();
+    golden().check("incremental_updates", &image).unwrap();
+}
+
+#[test]
+fn transforms() {
+    let mut headless = headless!();
+    let canvas = &mut headless.canvas;
+    let rotated = canvas.quad(-0.5, 0.5, 0.5, 0.5);
+    canvas.color(&rotated, [1.0, 0.0, 0.0, 1.0]).unwrap();
+    canvas
+        .set_angle(&rotated, std::f32::consts::FRAC_PI_4)
+        .unwrap();
+    let scaled = canvas.quad(0.5, 0.5, 0.5, 0.5);
+    canvas.color(&scaled, [0.0, 1.0, 0.0, 1.0]).unwrap();
+    canvas.set_scale(&scaled, [1.5, 0.5, 1.0]).unwrap();
+    // Rotated around its top left corner instead of its center
+    let pivoted = canvas.quad(0.0, -0.5, 0.5, 0.5);
+    canvas.color(&pivoted, [0.0, 0.0, 1.0, 1.0]).unwrap();
+    canvas.set_pivot(&pivoted, [-0.25, 0.25, 0.0]).unwrap();
+    canvas
+        .set_angle(&pivoted, -std::f32::consts::FRAC_PI_6)
+        .unwrap();
+    headless.render().unwrap();
+
+    // Moving a mesh after it was drawn only uploads its transform again
+    let canvas = &mut headless.canvas;
+    canvas.translate(&pivoted, [-0.25, 0.0, 0.0]).unwrap();
+    assert_eq!(
+        canvas.transform(&pivoted).unwrap().translation,
+        [-0.25, -0.5, 0.0]
+    );
+    let image = headless.render().unwrap();
+    golden().check("transforms", &image).unwrap();
+}
+
+#[test]
+fn swapping_canvases() {
+    let mut headless = headless!();
+    let red = headless.canvas.quad(-0.5, 0.0, 0.5, 0.5);
+    headless.canvas.color(&red, [1.0, 0.0, 0.0, 1.0]).unwrap();
+    let first = headless.render().unwrap();
+
+    // The other canvas overwrites the GPU buffers, so the first one is uploaded in full
+    // when it comes back even though nothing in it changed
+    let mut other = Canvas::new();
+    let green = other.quad(0.5, 0.0, 0.5, 0.5);
+    other.color(&green, [0.0, 1.0, 0.0, 1.0]).unwrap();
+    let first_canvas = std::mem::replace(&mut headless.canvas, other);
+    let second = headless.render().unwrap();
+    assert!(first != second);
+    headless.canvas = first_canvas;
+    assert!(headless.render().unwrap() == first);
+}
+
+fn mono_font() -> Font {
+    Font::from_bytes(include_bytes!("fonts/DejaVuSansMono.ttf").to_vec()).unwrap()
+}
+
+#[test]
+fn text_meshes() {
+    let font = mono_font();
+    let mut canvas = Canvas::new();
+    let text = canvas.text(&font, "Hi there", 0.0, 0.0, 0.1);
+    // One quad per visible glyph, the space has none
+    assert_eq!(canvas.vertices().len(), 7 * 4);
+    assert_eq!(canvas.indices().len(), 7 * 6);
+    assert!(canvas.vertices().iter().all(|vertex| {
+        vertex.color == [1.0; 4]
+            && vertex.mix_factor == 1.0
+            && vertex.tex_coords.iter().all(|t| (0.0..=1.0).contains(t))
+    }));
+    // The glyphs are placed left to right
+    let lefts = canvas
+        .vertices()
+        .chunks(4)
+        .map(|quad| quad[0].position[0])
+        .collect::<Vec<_>>();
+    assert!(lefts.windows(2).all(|pair| pair[0] < pair[1]));
+    canvas.color(&text, [1.0, 0.0, 0.0, 1.0]).unwrap();
+
+    let empty = canvas.text(&font, " \n\t", 0.0, 0.0, 0.1);
+    assert!(canvas.contains(&empty));
+    assert_eq!(canvas.vertices().len(), 7 * 4);
+}
+
+#[test]
+fn text() {
+    let mut headless = headless!();
+    let font = mono_font().with_pixel_size(16.0);
+    let canvas = &mut headless.canvas;
+    let first = canvas.text(&font, "maple", -0.9, 0.3, 0.35);
+    canvas.color(&first, [1.0, 1.0, 0.0, 1.0]).unwrap();
+    let second = canvas.text(&font, "a\nb", -0.2, -0.2, 0.35);
+    canvas.color(&second, [0.0, 1.0, 1.0, 1.0]).unwrap();
+    let image = headless.render().unwrap();
+    golden().check("text", &image).unwrap();
+}
+
+#[test]
+fn textured_meshes_are_white() {
+    let mut canvas = Canvas::new();
+    let texture = canvas
+        .add_texture(image::RgbaImage::new(2, 2), TextureOptions::default())
+        .unwrap();
+    let quad = canvas.quad(0.0, 0.0, 1.0, 1.0);
+    let triangle = canvas
+        .mesh(
+            MeshBuilder::new(
+                vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+                vec![0, 1, 2],
+            )
+            .with_texture(texture),
+        )
+        .unwrap();
+    canvas.texture(&quad, texture).unwrap();
+    assert!(canvas
+        .vertices()
+        .iter()
+        .all(|vertex| vertex.color == [1.0; 4]));
+
+    // Colors set before texturing are kept and tint the texture
+    canvas.color(&triangle, [0.0, 1.0, 0.0, 1.0]).unwrap();
+    canvas.texture(&triangle, texture).unwrap();
+    assert!(canvas.vertices()[4..]
+        .iter()
+        .all(|vertex| vertex.color == [0.0, 1.0, 0.0, 1.0] && vertex.mix_factor == 1.0));
+}
+
+#[test]
+fn default_tex_coords_are_packed() {
+    let mut canvas = Canvas::new();
+    let options = TextureOptions::default();
+    canvas
+        .add_texture(image::RgbaImage::new(4, 4), options.clone())
+        .unwrap();
+    let texture = canvas
+        .add_texture(image::RgbaImage::new(4, 4), options)
+        .unwrap();
+    let positions = vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+    canvas
+        .mesh(MeshBuilder::new(positions.clone(), vec![0, 1, 2]).with_texture(texture))
+        .unwrap();
+    canvas
+        .mesh(
+            MeshBuilder::new(positions, vec![0, 1, 2])
+                .with_tex_coords(vec![[0.0, 0.0]; 3])
+                .with_texture(texture),
+        )
+        .unwrap();
+    let vertices = canvas.vertices();
+    assert_ne!(vertices[0].tex_coords, [0.0, 0.0]);
+    for (default, explicit) in vertices[..3].iter().zip(&vertices[3..]) {
+        assert_eq!(default.tex_coords, explicit.tex_coords);
+    }
+}
+
+#[test]
+fn sprite_sheets() {
+    let mut canvas = Canvas::new();
+    let options = TextureOptions::nearest();
+    canvas
+        .add_texture(image::RgbaImage::new(3, 3), options)
+        .unwrap();
+    let texture = canvas
+        .add_texture(image::RgbaImage::new(8, 12), options)
+        .unwrap();
+    let sheet = canvas.sprite_sheet(texture).unwrap().with_grid(4, 4);
+    assert_eq!(sheet.len(), 6);
+    assert!(matches!(
+        Canvas::new().sprite_sheet(texture),
+        Err(Error::TextureNotFound(_))
+    ));
+
+    // Showing frames doesn't wear down the texture coordinates
+    let reference = canvas.quad(0.0, 0.0, 1.0, 1.0);
+    canvas.texture(&reference, texture).unwrap();
+    let quad = canvas.quad(0.0, 0.0, 1.0, 1.0);
+    for _ in 0..1000 {
+        for frame in 0..sheet.len() {
+            canvas.sprite(&quad, &sheet, frame).unwrap();
+        }
+    }
+    canvas.texture(&quad, texture).unwrap();
+    let vertices = canvas.vertices();
+    for (expected, actual) in vertices[..4].iter().zip(&vertices[4..]) {
+        assert_eq!(expected.tex_coords, actual.tex_coords);
+    }
+}
+
+#[test]
+fn textured_quads() {
+    let mut headless = headless!();
+    let canvas = &mut headless.canvas;
+    let checker = image::RgbaImage::from_fn(4, 4, |x, y| {
+        if (x + y) % 2 == 0 {
+            image::Rgba([255, 255, 255, 255])
+        } else {
+            image::Rgba([255, 0, 255, 255])
+        }
+    });
+    let checker = canvas
+        .add_texture(checker, TextureOptions::nearest())
+        .unwrap();
+    let gradient = image::RgbaImage::from_fn(8, 1, |x, _| image::Rgba([x as u8 * 32, 128, 0, 255]));
+    let gradient = canvas
+        .add_texture(gradient, TextureOptions::linear())
+        .unwrap();
+
+    let left = canvas.quad(-0.5, 0.5, 0.8, 0.8);
+    canvas.texture(&left, checker).unwrap();
+    let plain = canvas.quad(0.0, 0.0, 0.5, 0.5);
+    canvas.color(&plain, [0.0, 0.0, 1.0, 1.0]).unwrap();
+    let right = canvas.quad(0.5, -0.5, 0.8, 0.8);
+    canvas.texture(&right, gradient).unwrap();
+    let tinted = canvas.quad(0.5, 0.5, 0.8, 0.8);
+    canvas.color(&tinted, [0.0, 1.0, 0.0, 1.0]).unwrap();
+    canvas.texture(&tinted, checker).unwrap();
+
+    let image = headless.render().unwrap();
+    golden().check("textured_quads", &image).unwrap();
+}
+
+/// Encodes the image as a PNG file in memory.
+fn png(image: image::DynamicImage) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    image
+        .write_to(&mut bytes, image::ImageOutputFormat::Png)
+        .unwrap();
+    bytes
+}
+
+#[test]
+fn converted_textures() {
+    let mut headless = headless!();
+    let canvas = &mut headless.canvas;
+    let rgb = image::RgbImage::from_fn(4, 4, |x, _| image::Rgb([x as u8 * 64, 0, 255]));
+    let gray = image::GrayImage::from_fn(4, 4, |_, y| image::Luma([y as u8 * 64]));
+    let rgb = canvas
+        .load_texture(
+            &png(image::DynamicImage::ImageRgb8(rgb)),
+            TextureOptions::nearest(),
+        )
+        .unwrap();
+    let gray = canvas
+        .load_texture(
+            &png(image::DynamicImage::ImageLuma8(gray)),
+            TextureOptions::nearest(),
+        )
+        .unwrap();
+
+    for (x, texture) in [(-0.5, rgb), (0.5, gray)].iter() {
+        let quad = canvas.quad(*x, 0.0, 0.8, 0.8);
+        canvas.texture(&quad, *texture).unwrap();
+    }
+
+    let image = headless.render().unwrap();
+    golden().check("converted_textures", &image).unwrap();
+}
+
+#[test]
+fn mesh_errors() {
+    let mut canvas = Canvas::new();
+    let positions = vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+
+    let partial = canvas.mesh(MeshBuilder::new(positions.clone(), vec![0, 1]));
+    assert!(matches!(partial, Err(Error::IndexCount(2))));
+    let out_of_bounds = canvas.mesh(MeshBuilder::new(positions.clone(), vec![0, 1, 3]));
+    assert!(matches!(
+        out_of_bounds,
+        Err(Error::IndexOutOfBounds { index: 3, len: 3 })
+    ));
+    let colors = canvas
+        .mesh(MeshBuilder::new(positions.clone(), vec![0, 1, 2]).with_colors(vec![[1.0; 4]; 2]));
+    assert!(matches!(
+        colors,
+        Err(Error::AttributeCount {
+            attribute: "colors",
+            count: 2,
+            expected: 3,
+        })
+    ));
+    let tex_coords = canvas.mesh(
+        MeshBuilder::new(positions.clone(), vec![0, 1, 2]).with_tex_coords(vec![[0.0, 0.0]; 4]),
+    );
+    assert!(matches!(
+        tex_coords,
+        Err(Error::AttributeCount {
+            attribute: "tex_coords",
+            count: 4,
+            expected: 3,
+        })
+    ));
+    // Invalid geometry leaves the canvas untouched
+    assert!(canvas.vertices().is_empty());
+    assert!(canvas.indices().is_empty());
+    assert_eq!(canvas.meshes().count(), 0);
+
+    let triangle = canvas.mesh(MeshBuilder::new(positions, vec![0, 1, 2]));
+    assert!(triangle.is_ok());
+    assert_eq!(canvas.vertices().len(), 3);
+}
+
+#[test]
+fn texture_errors() {
+    let mut canvas = Canvas::new();
+    let empty = canvas.add_texture(image::RgbaImage::new(0, 4), TextureOptions::default());
+    assert!(matches!(empty, Err(Error::TextureSize { .. })));
+    let garbage = canvas.load_texture(b"not an image", TextureOptions::default());
+    assert!(matches!(garbage, Err(Error::ImageError(_))));
+    let missing = canvas.open_texture("tests/golden/missing.png", TextureOptions::default());
+    assert!(matches!(missing, Err(Error::ImageError(_))));
+    let anisotropy = canvas.add_texture(
+        image::RgbaImage::new(4, 4),
+        TextureOptions::linear().with_anisotropy(3),
+    );
+    assert!(matches!(anisotropy, Err(Error::Anisotropy(3))));
+    let texture = canvas
+        .add_texture(image::RgbaImage::new(4, 4), TextureOptions::default())
+        .unwrap();
+    let update = image::RgbaImage::new(2, 2);
+    for &(x, y) in &[(3, 0), (0, 3), (u32::MAX, 0), (0, u32::MAX - 1)] {
+        assert!(matches!(
+            canvas.update_texture(texture, x, y, &update),
+            Err(Error::UpdateOutOfBounds { .. })
+        ));
+    }
+    canvas.update_texture(texture, 2, 2, &update).unwrap();
+    canvas
+        .add_texture(
+            image::RgbaImage::new(4, 4),
+            TextureOptions::linear().with_anisotropy(16),
+        )
+        .unwrap();
+}
+
+#[test]
+fn sampler_options() {
+    let mut headless = headless!();
+    let canvas = &mut headless.canvas;
+    let checker = image::RgbaImage::from_fn(2, 2, |x, y| {
+        let v = if (x + y) % 2 == 0 { 255 } else { 0 };
+        image::Rgba([v, v, v, 255])
+    });
+    let nearest = canvas
+        .add_texture(checker.clone(), TextureOptions::nearest())
+        .unwrap();
+    let linear = canvas
+        .add_texture(checker.clone(), TextureOptions::linear())
+        .unwrap();
+    let repeat = canvas
+        .add_texture(checker, TextureOptions::nearest().with_wrap(Wrap::Repeat))
+        .unwrap();
+
+    for (x, texture) in [(-0.6, nearest), (0.0, linear)].iter() {
+        let quad = canvas.quad(*x, 0.5, 0.5, 0.5);
+        canvas.texture(&quad, *texture).unwrap();
+    }
+    let tiled = MeshBuilder::new(
+        vec![
+            [-0.9, 0.0, 0.0],
+            [0.9, 0.0, 0.0],
+            [0.9, -0.9, 0.0],
+            [-0.9, -0.9, 0.0],
+        ],
+        vec![0, 3, 2, 0, 2, 1],
+    )
+    .with_tex_coords(vec![[0.0, 0.0], [4.0, 0.0], [4.0, 2.0], [0.0, 2.0]])
+    .with_mix_factor(1.0)
+    .with_texture(repeat);
+    canvas.mesh(tiled).unwrap();
+
+    let image = headless.render().unwrap();
+    golden().check("sampler_options", &image).unwrap();
+}
+
+#[test]
+fn mipmaps() {
+    let mut headless = headless!();
+    let canvas = &mut headless.canvas;
+    let checker = image::RgbaImage::from_fn(256, 256, |x, y| {
+        let v = if (x + y) % 2 == 0 { 255 } else { 0 };
+        image::Rgba([v, v, v, 255])
+    });
+    let aliased = canvas
+        .add_texture(checker.clone(), TextureOptions::linear())
+        .unwrap();
+    let mipmapped = canvas
+        .add_texture(checker, TextureOptions::linear().with_mipmaps(true))
+        .unwrap();
+
+    for (x, texture) in [(-0.5, aliased), (0.5, mipmapped)].iter() {
+        let quad = canvas.quad(*x, 0.0, 0.8, 0.8);
+        canvas.texture(&quad, *texture).unwrap();
+    }
+
+    let image = headless.render().unwrap();
+    golden().check("mipmaps", &image).unwrap();
+}
+
+#[test]
+fn texture_updates() {
+    let mut headless = headless!();
+    let red = image::RgbaImage::from_pixel(8, 8, image::Rgba([255, 0, 0, 255]));
+    let blue = image::RgbaImage::from_pixel(4, 4, image::Rgba([0, 0, 255, 255]));
+    let green = image::RgbaImage::from_pixel(4, 4, image::Rgba([0, 255, 0, 255]));
+    let canvas = &mut headless.canvas;
+    let packed = canvas
+        .add_texture(red.clone(), TextureOptions::nearest())
+        .unwrap();
+    let streamed = canvas
+        .add_texture(
+            red,
+            TextureOptions::nearest()
+                .with_wrap(Wrap::Repeat)
+                .with_double_buffering(true),
+        )
+        .unwrap();
+    for (texture, x) in [(packed, -0.5), (streamed, 0.5)].iter() {
+        let quad = canvas.quad(*x, 0.0, 0.8, 0.8);
+        canvas.texture(&quad, *texture).unwrap();
+    }
+    headless.render().unwrap();
+
+    let canvas = &mut headless.canvas;
+    canvas.update_texture(packed, 4, 4, &blue).unwrap();
+    canvas.update_texture(streamed, 4, 4, &blue).unwrap();
+    headless.render().unwrap();
+    // The second copy of the double buffered texture must also get the first update
+    let canvas = &mut headless.canvas;
+    canvas.update_texture(packed, 0, 0, &green).unwrap();
+    canvas.update_texture(streamed, 0, 0, &green).unwrap();
+    assert!(matches!(
+        canvas.update_texture(streamed, 6, 0, &green),
+        Err(Error::UpdateOutOfBounds { .. })
+    ));
+
+    let image = headless.render().unwrap();
+    golden().check("texture_updates", &image).unwrap();
+}
+
+#[test]
+fn framebuffer() {
+    let mut headless = headless!();
+    let canvas = &mut headless.canvas;
+    canvas.set_framebuffer(Some(Framebuffer::new(16, 12).unwrap()));
+    let quad = canvas.quad(0.0, 0.0, 0.25, 0.25);
+    canvas.color(&quad, [1.0, 1.0, 1.0, 1.0]).unwrap();
+    headless.render().unwrap();
+
+    // Scaled 4 times to 64x48, leaving black bars above and below
+    let framebuffer = headless.canvas.framebuffer_mut().unwrap();
+    assert_eq!(framebuffer.scaling(), Scaling::Integer);
+    framebuffer.clear(image::Rgba([0, 0, 255, 255]));
+    for x in 0..12 {
+        framebuffer.put_pixel(x, x, image::Rgba([255, 0, 0, 255]));
+    }
+    let image = headless.render().unwrap();
+    golden().check("framebuffer", &image).unwrap();
+}
+
+#[test]
+fn camera() {
+    let mut headless = headless!();
+    let canvas = &mut headless.canvas;
+    let left = canvas.quad(-2.0, 0.0, 1.0, 1.0);
+    canvas.color(&left, [1.0, 0.0, 0.0, 1.0]).unwrap();
+    let right = canvas.quad(2.0, 0.0, 1.0, 1.0);
+    canvas.color(&right, [0.0, 0.0, 1.0, 1.0]).unwrap();
+    // Neither quad is visible in the default -1..1 view
+    headless.render().unwrap();
+
+    headless.camera.fit(&Rect {
+        min: [-2.5, -0.5],
+        max: [2.5, 0.5],
+    });
+    headless.camera.zoom_at(2.0, [2.0, 0.0]);
+    headless.camera.pan(0.0, 0.5);
+    let image = headless.render().unwrap();
+    golden().check("camera", &image).unwrap();
+}
+
+#[test]
+fn projection_modes() {
+    let mut headless = headless!();
+    headless.resize(WIDTH, HEIGHT / 2);
+    let canvas = &mut headless.canvas;
+    // Covers the view except with one unit per pixel, where it is 16 pixels wide
+    let square = canvas.quad(0.0, 0.0, 16.0, 16.0);
+    canvas.color(&square, [1.0, 0.0, 0.0, 1.0]).unwrap();
+    let circle = canvas.circle(0.0, 0.0, 0.9);
+    canvas.color(&circle, [0.0, 1.0, 0.0, 1.0]).unwrap();
+    let modes = [
+        ("projection_stretch", ProjectionMode::Stretch),
+        ("projection_fit", ProjectionMode::Fit),
+        ("projection_fill", ProjectionMode::Fill),
+        ("projection_pixels", ProjectionMode::Pixels),
+    ];
+    for (name, mode) in modes.iter() {
+        headless.camera.mode = *mode;
+        let image = headless.render().unwrap();
+        golden().check(name, &image).unwrap();
+    }
+}
+
+#[test]
+fn sprite_frames() {
+    let mut headless = headless!();
+    let canvas = &mut headless.canvas;
+    let colors = [
+        [255, 0, 0, 255],
+        [0, 255, 0, 255],
+        [0, 0, 255, 255],
+        [255, 255, 0, 255],
+    ];
+    let sheet = image::RgbaImage::from_fn(8, 8, |x, y| {
+        image::Rgba(colors[(y / 4 * 2 + x / 4) as usize])
+    });
+    let texture = canvas
+        .add_texture(sheet, TextureOptions::nearest())
+        .unwrap();
+    let sheet = canvas.sprite_sheet(texture).unwrap().with_grid(4, 4);
+
+    let still = canvas.quad(-0.5, 0.5, 0.8, 0.8);
+    canvas.sprite(&still, &sheet, 1).unwrap();
+    let animated = canvas.quad(0.5, -0.5, 0.8, 0.8);
+    canvas
+        .animate(&animated, &sheet, &Animation::all(&sheet, 4.0))
+        .unwrap();
+    canvas.advance(0.6);
+
+    let image = headless.render().unwrap();
+    golden().check("sprite_frames", &image).unwrap();
+}
+
+#[test]
+fn shapes() {
+    let mut headless = headless!();
+    let canvas = &mut headless.canvas;
+    let circle = canvas.circle(-0.5, 0.5, 0.3);
+    canvas.color(&circle, [1.0, 0.0, 0.0, 1.0]).unwrap();
+    let pie = canvas.pie(0.5, 0.5, 0.3, 0.0, 4.0);
+    canvas.color(&pie, [0.0, 1.0, 0.0, 1.0]).unwrap();
+    let rect = canvas.rounded_rect(-0.5, -0.5, 0.6, 0.4, 0.1);
+    canvas.color(&rect, [0.0, 0.0, 1.0, 1.0]).unwrap();
+    let arrow = canvas
+        .polygon(
+            0.5,
+            -0.5,
+            &[[-0.3, -0.3], [0.0, 0.3], [0.3, -0.3], [0.0, -0.1]],
+        )
+        .unwrap();
+    canvas.color(&arrow, [1.0, 1.0, 0.0, 1.0]).unwrap();
+    let image = headless.render().unwrap();
+    golden().check("shapes", &image).unwrap();
+}
+
+#[test]
+fn strokes() {
+    let mut headless = headless!();
+    let canvas = &mut headless.canvas;
+    let zigzag = [[-0.8, 0.2], [-0.4, 0.8], [0.0, 0.2], [0.4, 0.8], [0.8, 0.2]];
+    let miter = StrokeStyle::new(0.1);
+    let line = canvas.polyline(&zigzag, false, &miter);
+    canvas.color(&line, [1.0, 0.0, 0.0, 1.0]).unwrap();
+    let round = StrokeStyle::new(0.1)
+        .with_join(LineJoin::Round)
+        .with_cap(LineCap::Round);
+    let triangle = [[-0.6, -0.8], [0.0, -0.2], [0.6, -0.8]];
+    let outline = canvas.polyline(&triangle, true, &round);
+    canvas.color(&outline, [0.0, 1.0, 0.0, 1.0]).unwrap();
+    let bevel = StrokeStyle::new(0.05)
+        .with_join(LineJoin::Bevel)
+        .with_cap(LineCap::Square);
+    let line = canvas.line([-0.8, 0.0], [0.8, 0.0], &bevel);
+    canvas.color(&line, [0.0, 0.0, 1.0, 1.0]).unwrap();
+    let image = headless.render().unwrap();
+    golden().check("strokes", &image).unwrap();
+}
+
+#[test]
+fn paths() {
+    let mut headless = headless!();
+    let canvas = &mut headless.canvas;
+    let heart = Path::new()
+        .move_to([-0.5, -0.8])
+        .cubic_to([-1.3, -0.3], [-0.9, 0.3], [-0.5, -0.1])
+        .cubic_to([-0.1, 0.3], [0.3, -0.3], [-0.5, -0.8])
+        .close();
+    let fill = canvas.fill_path(&heart, FillRule::NonZero);
+    canvas.color(&fill, [1.0, 0.0, 0.0, 1.0]).unwrap();
+    let ring = Path::new()
+        .move_to([0.9, 0.5])
+        .arc_to([0.4, 0.4], 0.0, true, true, [0.1, 0.5])
+        .arc_to([0.4, 0.4], 0.0, true, true, [0.9, 0.5])
+        .close()
+        .move_to([0.7, 0.5])
+        .arc_to([0.2, 0.2], 0.0, true, true, [0.3, 0.5])
+        .arc_to([0.2, 0.2], 0.0, true, true, [0.7, 0.5])
+        .close();
+    let fill = canvas.fill_path(&ring, FillRule::EvenOdd);
+    canvas.color(&fill, [0.0, 1.0, 0.0, 1.0]).unwrap();
+    let wave = Path::new()
+        .move_to([0.1, -0.5])
+        .quad_to([0.3, 0.0], [0.5, -0.5])
+        .quad_to([0.7, -1.0], [0.9, -0.5]);
+    let stroke = canvas.stroke_path(&wave, &StrokeStyle::new(0.05));
+    canvas.color(&stroke, [0.0, 0.0, 1.0, 1.0]).unwrap();
+    let image = headless.render().unwrap();
+    golden().check("paths", &image).unwrap();
+}
+
+#[test]
+fn svg() {
+    let mut headless = headless!();
+    let svg = Svg::parse(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24">
+            <g transform="rotate(15 12 12)">
+                <rect x="3" y="3" width="18" height="18" rx="3" fill="#3366cc"/>
+                <circle id="dot" cx="12" cy="12" r="5" fill="none" stroke="#ffffff" stroke-width="2"/>
+            </g>
+            <path d="M4 20 Q12 14 20 20" fill="none" stroke="orange" stroke-linecap="round"/>
+        </svg>"##,
+    )
+    .unwrap();
+    let elements = svg.draw(&mut headless.canvas, 0.0, 0.0, 1.8).unwrap();
+    let dot = elements[1].stroke.unwrap();
+    headless.canvas.color(&dot, [1.0, 1.0, 0.0, 1.0]).unwrap();
+    let image = headless.render().unwrap();
+    golden().check("svg", &image).unwrap();
+}